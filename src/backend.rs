@@ -0,0 +1,543 @@
+// The reflection results the rest of the crate works from. These are
+// filled in by spirv-reflect or, with the `native-reflect` feature or
+// without spirv-reflect, by the pure Rust reader in `native.rs`.
+use crate::layouts::{BlockMember, ExecutionModel, MemberType};
+use spirv_headers as spv;
+
+#[cfg(all(feature = "spirv-reflect", not(feature = "native-reflect")))]
+pub use self::spirv_reflect::ShaderModule;
+#[cfg(any(feature = "native-reflect", not(feature = "spirv-reflect")))]
+pub use crate::native::ShaderModule;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryPointInfo {
+    pub name: String,
+    /// `None` for stages vulkano doesn't have, like ray tracing
+    pub execution_model: Option<ExecutionModel>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterfaceVariable {
+    pub spirv_id: u32,
+    pub name: String,
+    /// The member's name when this is a member of a struct
+    pub member_name: String,
+    pub location: u32,
+    /// Set when the variable or any of its members is a built-in
+    pub built_in: bool,
+    pub ty: MemberType,
+    /// The length of each array dimension, outermost first
+    pub array: Vec<u32>,
+    pub members: Vec<InterfaceVariable>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorSet {
+    pub set: u32,
+    pub bindings: Vec<DescriptorBinding>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorBinding {
+    pub spirv_id: u32,
+    pub name: String,
+    pub type_name: String,
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    /// Images, texel buffers and input attachments have these
+    pub image: Option<ImageTraits>,
+    /// The product of the array dimensions
+    pub count: u32,
+    /// The members of a uniform or storage buffer
+    pub members: Vec<BlockMember>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorType {
+    Sampler,
+    CombinedImageSampler,
+    SampledImage,
+    StorageImage,
+    UniformTexelBuffer,
+    StorageTexelBuffer,
+    UniformBuffer,
+    StorageBuffer,
+    InputAttachment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageTraits {
+    pub dim: spv::Dim,
+    pub arrayed: bool,
+    pub multisampled: bool,
+    /// Used with a sampler rather than as a storage image
+    pub sampled: bool,
+    pub format: spv::ImageFormat,
+}
+
+/// A push constant block
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockVariable {
+    pub spirv_id: u32,
+    pub type_name: String,
+    pub size: u32,
+    pub members: Vec<BlockMember>,
+}
+
+#[cfg(all(feature = "spirv-reflect", any(test, not(feature = "native-reflect"))))]
+mod spirv_reflect {
+    use super::*;
+    use crate::error::{Error, ReflectError};
+    use crate::layouts::ScalarType;
+    use crate::spirv;
+    use ::spirv_reflect as sr;
+    use sr::types::{
+        ReflectBlockVariable, ReflectDecorationFlags as DF, ReflectDescriptorType,
+        ReflectDimension, ReflectImageFormat, ReflectNumericTraits, ReflectShaderStageFlags,
+        ReflectTypeFlags as TF,
+    };
+    use std::collections::HashSet;
+
+    pub struct ShaderModule {
+        reflect: sr::ShaderModule,
+        // For what spirv-reflect leaves out, like runtime sized arrays
+        module: spirv::Module,
+    }
+
+    fn loading(e: &str) -> Error {
+        Error::LoadingData(e.to_string())
+    }
+
+    impl ShaderModule {
+        /// spirv-reflect rejects `BuiltIn` decorations on constants, like the
+        /// `WorkgroupSize` glslang declares for `local_size_x_id`, so those
+        /// are dropped first. spirv-reflect doesn't use them anyway.
+        pub fn load(data: &[u32]) -> Result<Self, Error> {
+            let module = spirv::Module::new(data)?;
+            let constants: HashSet<u32> = module
+                .instructions
+                .iter()
+                .filter(|i| spirv::declares_constant(i.op))
+                .map(|i| i.operands[1])
+                .collect();
+            let mut words = data[..5].to_vec();
+            let mut rest = &data[5..];
+            while !rest.is_empty() {
+                let count = (rest[0] >> 16) as usize;
+                let built_in_constant = rest[0] & 0xffff == spv::Op::Decorate as u32
+                    && count > 2
+                    && rest[2] == spv::Decoration::BuiltIn as u32
+                    && constants.contains(&rest[1]);
+                if !built_in_constant {
+                    words.extend_from_slice(&rest[..count]);
+                }
+                rest = &rest[count..];
+            }
+            let reflect = sr::ShaderModule::load_u32_data(&words).map_err(loading)?;
+            Ok(ShaderModule { reflect, module })
+        }
+
+        // Tests also build this next to the pure Rust reader
+        #[cfg(not(feature = "native-reflect"))]
+        pub fn module(&self) -> &spirv::Module {
+            &self.module
+        }
+
+        pub fn entry_points(&self) -> Result<Vec<EntryPointInfo>, Error> {
+            Ok(self
+                .reflect
+                .enumerate_entry_points()
+                .map_err(loading)?
+                .into_iter()
+                .map(|ep| EntryPointInfo {
+                    execution_model: execution_model(ep.shader_stage),
+                    name: ep.name,
+                })
+                .collect())
+        }
+
+        /// The stage of the first entry point
+        pub fn execution_model(&self) -> Option<ExecutionModel> {
+            execution_model(self.reflect.get_shader_stage())
+        }
+
+        pub fn input_variables(
+            &self,
+            entry_point: Option<&str>,
+        ) -> Result<Vec<InterfaceVariable>, Error> {
+            let vars = self
+                .reflect
+                .enumerate_input_variables(entry_point)
+                .map_err(loading)?;
+            vars.iter().map(interface_variable).collect()
+        }
+
+        pub fn output_variables(
+            &self,
+            entry_point: Option<&str>,
+        ) -> Result<Vec<InterfaceVariable>, Error> {
+            let vars = self
+                .reflect
+                .enumerate_output_variables(entry_point)
+                .map_err(loading)?;
+            vars.iter().map(interface_variable).collect()
+        }
+
+        pub fn descriptor_sets(
+            &self,
+            entry_point: Option<&str>,
+        ) -> Result<Vec<DescriptorSet>, Error> {
+            let sets = self
+                .reflect
+                .enumerate_descriptor_sets(entry_point)
+                .map_err(loading)?;
+            sets.into_iter()
+                .map(|s| {
+                    let bindings = s
+                        .bindings
+                        .into_iter()
+                        .map(|b| {
+                            let (descriptor_type, image) = descriptor_type(b.descriptor_type)
+                                .ok_or(Error::Reflect(ReflectError::DescriptorType {
+                                    id: b.spirv_id,
+                                }))?;
+                            Ok(DescriptorBinding {
+                                spirv_id: b.spirv_id,
+                                type_name: b
+                                    .type_description
+                                    .map(|t| t.type_name)
+                                    .unwrap_or_default(),
+                                binding: b.binding,
+                                descriptor_type,
+                                image: if image { image_traits(&b.image) } else { None },
+                                count: b.count,
+                                members: self.block_members(&b.block),
+                                name: b.name,
+                            })
+                        })
+                        .collect::<Result<_, Error>>()?;
+                    Ok(DescriptorSet {
+                        set: s.set,
+                        bindings,
+                    })
+                })
+                .collect()
+        }
+
+        pub fn push_constant_blocks(
+            &self,
+            entry_point: Option<&str>,
+        ) -> Result<Vec<BlockVariable>, Error> {
+            let blocks = self
+                .reflect
+                .enumerate_push_constant_blocks(entry_point)
+                .map_err(loading)?;
+            Ok(blocks
+                .iter()
+                .map(|pc| BlockVariable {
+                    spirv_id: pc.spirv_id,
+                    type_name: pc
+                        .type_description
+                        .as_ref()
+                        .map(|t| t.type_name.clone())
+                        .unwrap_or_default(),
+                    size: pc.size,
+                    members: self.block_members(pc),
+                })
+                .collect())
+        }
+
+        // spirv-reflect doesn't report runtime sized
+        // arrays so those come from the SPIR-V types
+        fn block_members(&self, block: &ReflectBlockVariable) -> Vec<BlockMember> {
+            block
+                .members
+                .iter()
+                .map(|m| {
+                    let type_id = m.type_description.as_ref().map(|t| t.id).unwrap_or(0);
+                    let flags = m
+                        .type_description
+                        .as_ref()
+                        .map(|t| t.type_flags)
+                        .unwrap_or(TF::UNDEFINED);
+                    let runtime_array = self
+                        .module
+                        .type_declaration(type_id)
+                        .map(|t| t.op == spv::Op::TypeRuntimeArray)
+                        .unwrap_or(false);
+                    let (array, array_stride) = if runtime_array {
+                        let stride = self
+                            .module
+                            .decoration(type_id, spv::Decoration::ArrayStride);
+                        (vec![0], stride.unwrap_or(0))
+                    } else {
+                        (m.array.dims.clone(), m.array.stride)
+                    };
+                    BlockMember {
+                        name: m.name.clone(),
+                        ty: member_type(flags, &m.numeric),
+                        offset: m.offset,
+                        size: m.size,
+                        array,
+                        array_stride,
+                        matrix_stride: m.numeric.matrix.stride,
+                        row_major: m.decoration_flags.contains(DF::ROW_MAJOR),
+                        members: self.block_members(m),
+                    }
+                })
+                .collect()
+        }
+    }
+
+    fn interface_variable(
+        var: &sr::types::ReflectInterfaceVariable,
+    ) -> Result<InterfaceVariable, Error> {
+        let flags = var
+            .type_description
+            .as_ref()
+            .map(|t| t.type_flags)
+            .unwrap_or(TF::UNDEFINED);
+        if !flags.intersects(TF::BOOL | TF::INT | TF::FLOAT | TF::STRUCT) {
+            let id = var.type_description.as_ref().map(|t| t.id).unwrap_or(0);
+            return Err(Error::Reflect(ReflectError::InterfaceType { id }));
+        }
+        Ok(InterfaceVariable {
+            spirv_id: var.spirv_id,
+            name: var.name.clone(),
+            member_name: var
+                .type_description
+                .as_ref()
+                .map(|t| t.struct_member_name.clone())
+                .unwrap_or_default(),
+            location: var.location,
+            built_in: var.decoration_flags.contains(DF::BUILT_IN),
+            ty: member_type(flags, &var.numeric),
+            array: var.array.dims.clone(),
+            members: var
+                .members
+                .iter()
+                .map(interface_variable)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn member_type(flags: TF, numeric: &ReflectNumericTraits) -> MemberType {
+        let width = numeric.scalar.width;
+        let scalar = if flags.contains(TF::BOOL) {
+            ScalarType::Bool
+        } else if flags.contains(TF::FLOAT) {
+            ScalarType::Float(width)
+        } else if numeric.scalar.signedness != 0 {
+            ScalarType::Sint(width)
+        } else {
+            ScalarType::Uint(width)
+        };
+        if flags.contains(TF::STRUCT) {
+            MemberType::Struct
+        } else if flags.contains(TF::MATRIX) {
+            MemberType::Matrix {
+                ty: scalar,
+                columns: numeric.matrix.column_count,
+                rows: numeric.matrix.row_count,
+            }
+        } else if flags.contains(TF::VECTOR) {
+            MemberType::Vector(scalar, numeric.vector.component_count)
+        } else {
+            MemberType::Scalar(scalar)
+        }
+    }
+
+    fn execution_model(stage: ReflectShaderStageFlags) -> Option<ExecutionModel> {
+        use ReflectShaderStageFlags as S;
+        match stage {
+            S::VERTEX => Some(ExecutionModel::Vertex),
+            S::TESSELLATION_CONTROL => Some(ExecutionModel::TessellationControl),
+            S::TESSELLATION_EVALUATION => Some(ExecutionModel::TessellationEvaluation),
+            S::GEOMETRY => Some(ExecutionModel::Geometry),
+            S::FRAGMENT => Some(ExecutionModel::Fragment),
+            S::COMPUTE => Some(ExecutionModel::GLCompute),
+            _ => None,
+        }
+    }
+
+    // The descriptor type and whether it's a kind of image. spirv-reflect
+    // never gives the dynamic buffers since the shader can't tell.
+    fn descriptor_type(ty: ReflectDescriptorType) -> Option<(DescriptorType, bool)> {
+        use DescriptorType as D;
+        use ReflectDescriptorType as R;
+        match ty {
+            R::Sampler => Some((D::Sampler, false)),
+            R::CombinedImageSampler => Some((D::CombinedImageSampler, true)),
+            R::SampledImage => Some((D::SampledImage, true)),
+            R::StorageImage => Some((D::StorageImage, true)),
+            R::UniformTexelBuffer => Some((D::UniformTexelBuffer, true)),
+            R::StorageTexelBuffer => Some((D::StorageTexelBuffer, true)),
+            R::UniformBuffer | R::UniformBufferDynamic => Some((D::UniformBuffer, false)),
+            R::StorageBuffer | R::StorageBufferDynamic => Some((D::StorageBuffer, false)),
+            R::InputAttachment => Some((D::InputAttachment, true)),
+            R::AccelerationStructureNV | R::Undefined => None,
+        }
+    }
+
+    fn image_traits(image: &sr::types::ReflectImageTraits) -> Option<ImageTraits> {
+        let dim = match image.dim {
+            ReflectDimension::Type1d => spv::Dim::Dim1D,
+            ReflectDimension::Type2d => spv::Dim::Dim2D,
+            ReflectDimension::Type3d => spv::Dim::Dim3D,
+            ReflectDimension::Cube => spv::Dim::DimCube,
+            ReflectDimension::Rect => spv::Dim::DimRect,
+            ReflectDimension::Buffer => spv::Dim::DimBuffer,
+            ReflectDimension::SubPassData => spv::Dim::DimSubpassData,
+            ReflectDimension::Undefined => return None,
+        };
+        Some(ImageTraits {
+            dim,
+            arrayed: image.arrayed != 0,
+            multisampled: image.ms != 0,
+            // 1 means used with a sampler, 2 means a storage image
+            sampled: image.sampled == 1,
+            format: image_format(image.image_format),
+        })
+    }
+
+    fn image_format(format: ReflectImageFormat) -> spv::ImageFormat {
+        use spv::ImageFormat as F;
+        use ReflectImageFormat::*;
+        match format {
+            Undefined => F::Unknown,
+            RGBA32_FLOAT => F::Rgba32f,
+            RGBA16_FLOAT => F::Rgba16f,
+            R32_FLOAT => F::R32f,
+            RGBA8 => F::Rgba8,
+            RGBA8_SNORM => F::Rgba8Snorm,
+            RG32_FLOAT => F::Rg32f,
+            RG16_FLOAT => F::Rg16f,
+            R11G11B10_FLOAT => F::R11fG11fB10f,
+            R16_FLOAT => F::R16f,
+            RGBA16 => F::Rgba16,
+            RGB10A2 => F::Rgb10A2,
+            RG16 => F::Rg16,
+            RG8 => F::Rg8,
+            R16 => F::R16,
+            R8 => F::R8,
+            RGBA16_SNORM => F::Rgba16Snorm,
+            RG16_SNORM => F::Rg16Snorm,
+            RG8_SNORM => F::Rg8Snorm,
+            R16_SNORM => F::R16Snorm,
+            R8_SNORM => F::R8Snorm,
+            RGBA32_INT => F::Rgba32i,
+            RGBA16_INT => F::Rgba16i,
+            RGBA8_INT => F::Rgba8i,
+            R32_INT => F::R32i,
+            RG32_INT => F::Rg32i,
+            RG16_INT => F::Rg16i,
+            RG8_INT => F::Rg8i,
+            R16_INT => F::R16i,
+            R8_INT => F::R8i,
+            RGBA32_UINT => F::Rgba32ui,
+            RGBA16_UINT => F::Rgba16ui,
+            RGBA8_UINT => F::Rgba8ui,
+            R32_UINT => F::R32ui,
+            RGB10A2_UINT => F::Rgb10a2ui,
+            RG32_UINT => F::Rg32ui,
+            RG16_UINT => F::Rg16ui,
+            RG8_UINT => F::Rg8ui,
+            R16_UINT => F::R16ui,
+            R8_UINT => F::R8ui,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "spirv-reflect"))]
+mod tests {
+    use super::spirv_reflect;
+    use crate::compiler;
+    use crate::native;
+    use shaderc::ShaderKind;
+    use std::fs;
+    use std::path::Path;
+
+    // Runs every test shader through both backends. They have to agree
+    // on everything, down to how spirv-reflect sizes block members.
+    #[test]
+    fn backends_agree() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/shaders");
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        paths.sort();
+        for path in paths {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let data = match path.extension().and_then(|e| e.to_str()) {
+                Some("glsl") => {
+                    let kind = if name.starts_with("vert") {
+                        ShaderKind::Vertex
+                    } else if name.starts_with("frag") {
+                        ShaderKind::Fragment
+                    } else if name.starts_with("compute") {
+                        ShaderKind::Compute
+                    } else {
+                        panic!("no stage for {}", name)
+                    };
+                    compiler::compile(&path, kind).unwrap()
+                }
+                Some("spv") => fs::read(&path)
+                    .unwrap()
+                    .chunks(4)
+                    .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+                    .collect(),
+                _ => continue,
+            };
+            compare(&name, &data);
+        }
+    }
+
+    fn compare(name: &str, data: &[u32]) {
+        let native = native::ShaderModule::load(data).unwrap();
+        let reflect = spirv_reflect::ShaderModule::load(data).unwrap();
+        let entry_points = native.entry_points().unwrap();
+        assert_eq!(entry_points, reflect.entry_points().unwrap(), "{}", name);
+        assert_eq!(
+            native.execution_model(),
+            reflect.execution_model(),
+            "{}",
+            name
+        );
+        // Each entry point and then the whole module
+        let names = entry_points
+            .iter()
+            .map(|ep| Some(ep.name.as_str()))
+            .chain(Some(None));
+        for ep in names {
+            assert_eq!(
+                native.descriptor_sets(ep).unwrap(),
+                reflect.descriptor_sets(ep).unwrap(),
+                "{} {:?}",
+                name,
+                ep
+            );
+            assert_eq!(
+                native.push_constant_blocks(ep).unwrap(),
+                reflect.push_constant_blocks(ep).unwrap(),
+                "{} {:?}",
+                name,
+                ep
+            );
+            assert_eq!(
+                native.input_variables(ep).unwrap(),
+                reflect.input_variables(ep).unwrap(),
+                "{} {:?}",
+                name,
+                ep
+            );
+            assert_eq!(
+                native.output_variables(ep).unwrap(),
+                reflect.output_variables(ep).unwrap(),
+                "{} {:?}",
+                name,
+                ep
+            );
+        }
+    }
+}
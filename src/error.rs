@@ -1,14 +1,144 @@
+use crate::layouts::{Interpolation, ScalarType, SpecValue};
+use crate::stats::{Limit, Stage};
+use crate::vk::descriptor::descriptor::DescriptorDesc;
+use crate::vk::device::{Features, RawDeviceExtensions};
+use crate::vk::format::{Format, FormatTy};
+
 #[derive(Debug)]
 pub enum Error {
     Compile(CompileError),
     Layout(ConvertError),
     LoadingData(String),
+    Reflect(ReflectError),
+    EntryPoint(String),
+    Merge(MergeError),
+    Interface(Vec<InterfaceError>),
+    Attachment(Vec<AttachmentMismatch>),
+    Override(OverrideError),
+    /// A runtime sized descriptor array was given no count
+    RuntimeArray { set: usize, binding: usize },
+    SpecConstant(SpecConstantError),
+    /// Stages whose stats go over a `Budget`
+    Budget(Vec<OverBudget>),
+    /// The features and extensions a shader needs that the device doesn't enable
+    Device {
+        features: Features,
+        extensions: RawDeviceExtensions,
+    },
     FileWatch(notify::Error),
 }
 
 #[derive(Debug)]
 pub enum ConvertError {
     Unimplemented(String),
+    /// An image dimension that can't be used by a sampled or storage image
+    Dimension(String),
+}
+
+/// Modules the reflection can't read
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReflectError {
+    /// The words don't start with the SPIR-V header
+    NotSpirv,
+    /// The instruction starting at this word has a bad word count or too few operands
+    Instruction { word: usize },
+    /// An interface variable of a type, given by its id, that isn't a number, vector, matrix or struct
+    InterfaceType { id: u32 },
+    /// A resource that isn't any kind of descriptor vulkano has
+    DescriptorType { id: u32 },
+}
+
+#[derive(Debug)]
+pub enum MergeError {
+    /// Two stages declare the same set and binding differently
+    Descriptor {
+        set: usize,
+        binding: usize,
+        first: DescriptorDesc,
+        second: DescriptorDesc,
+    },
+    /// Two stages lay out the push constant block differently
+    PushConstant { offset: usize },
+}
+
+#[derive(Debug)]
+pub enum InterfaceError {
+    /// The fragment stage reads a location the vertex stage doesn't output
+    Missing { location: u32, name: String },
+    Format {
+        location: u32,
+        vertex: Format,
+        fragment: Format,
+    },
+    Interpolation {
+        location: u32,
+        vertex: Interpolation,
+        fragment: Interpolation,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttachmentMismatch {
+    /// The fragment shader writes a location that has no attachment
+    NoAttachment { location: u32 },
+    /// The fragment shader never writes the attachment at this location
+    Unwritten { location: u32 },
+    /// The fragment shader writes fewer components than the attachment has
+    Components {
+        location: u32,
+        shader: u32,
+        attachment: u32,
+    },
+    /// One side is float and the other is a signed or unsigned integer
+    NumericType {
+        location: u32,
+        shader: FormatTy,
+        attachment: FormatTy,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverrideError {
+    /// No vertex input is at this location
+    Missing { location: u32 },
+    /// The shader can't read its input from data in this format
+    Incompatible {
+        location: u32,
+        shader: Format,
+        format: Format,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpecConstantError {
+    /// No specialization constant has this name
+    Missing(String),
+    /// No specialization constant has this `constant_id`
+    MissingId(u32),
+    /// The value is the wrong kind for the constant or doesn't fit in it
+    Type {
+        name: String,
+        ty: ScalarType,
+        value: SpecValue,
+    },
+    /// Only booleans and 32 bit numbers with an id below
+    /// `SpecConstants::SLOTS` can be specialized
+    Unsupported { id: u32, ty: ScalarType },
+}
+
+/// A stage that has more of something than its budget allows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverBudget {
+    pub stage: Stage,
+    pub limit: Limit,
+    pub value: usize,
+    pub max: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The vertex stage declares this output but never writes to it
+    Unwritten { location: u32, name: String },
 }
 
 #[derive(Debug)]
@@ -1,9 +1,16 @@
 use crate::vk;
 use vk::pipeline::shader::*;
 pub use vk::pipeline::shader::ShaderInterfaceDef;
+pub use spirv_headers::{BuiltIn, Capability};
+use vk::device::{Features, RawDeviceExtensions};
 use vk::descriptor::descriptor::*;
 use vk::descriptor::pipeline_layout::*;
 use crate::reflection::LayoutData;
+use crate::validation;
+use vk::format::Format;
+use crate::error::{Error, MergeError, OverrideError, SpecConstantError, Warning};
+use std::convert::TryFrom;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Default)]
 pub struct Entry {
@@ -14,6 +21,659 @@ pub struct Entry {
     pub vert_output: VertOutput,
     pub vert_layout: VertLayout,
     pub compute_layout: ComputeLayout,
+    pub vert_info: StageInfo,
+    pub frag_info: StageInfo,
+    pub compute_info: StageInfo,
+    /// The `local_size` of a compute shader
+    pub workgroup_size: Option<WorkgroupSize>,
+    pub warnings: Vec<Warning>,
+}
+
+impl Entry {
+    /// Combines the layouts of every stage into one pipeline layout.
+    /// Each binding is visible to the stages that use it.
+    pub fn layout(&self) -> Result<EntryLayout, Error> {
+        let stages = [
+            &self.vert_layout.layout_data,
+            &self.frag_layout.layout_data,
+            &self.compute_layout.layout_data,
+        ];
+        stages
+            .iter()
+            .try_fold(LayoutData::default(), |merged, data| merge_layouts(merged, data))
+            .map(|layout_data| EntryLayout { layout_data })
+    }
+
+    /// The push constant blocks of every stage. A block
+    /// declared the same way in several stages is listed once.
+    pub fn push_constants(&self) -> Vec<PushConstantBlock> {
+        let mut blocks: Vec<PushConstantBlock> = Vec::new();
+        let stages = [
+            &self.vert_layout.layout_data,
+            &self.frag_layout.layout_data,
+            &self.compute_layout.layout_data,
+        ];
+        for block in stages.iter().flat_map(|data| &data.push_constants) {
+            if !blocks.contains(block) {
+                blocks.push(block.clone());
+            }
+        }
+        blocks
+    }
+
+    /// The device features that every stage needs between them.
+    pub fn required_features(&self) -> Features {
+        let capabilities: Vec<Capability> = self
+            .stage_infos()
+            .iter()
+            .flat_map(|info| info.capabilities.iter().copied())
+            .collect();
+        validation::required_features(&capabilities)
+    }
+
+    /// The device extensions that every stage needs between them.
+    pub fn required_extensions(&self) -> RawDeviceExtensions {
+        self.stage_infos()
+            .iter()
+            .fold(RawDeviceExtensions::none(), |extensions, info| {
+                extensions.union(&info.required_extensions())
+            })
+    }
+
+    /// Checks the features and extensions a device was created with
+    /// cover what the shaders need, so a reloaded shader that starts
+    /// using something new fails here rather than at pipeline creation.
+    pub fn check_device(&self, features: &Features, extensions: &RawDeviceExtensions) -> Result<(), Error> {
+        validation::check_device(
+            &self.required_features(),
+            &self.required_extensions(),
+            features,
+            extensions,
+        )
+    }
+
+    /// Finds a descriptor by the name the shaders give it, so descriptor
+    /// sets don't depend on binding numbers. The stages are those that
+    /// declare it.
+    pub fn binding_by_name(&self, name: &str) -> Option<NamedBinding> {
+        let mut found: Option<NamedBinding> = None;
+        for (stages, data) in self.stage_layouts().iter() {
+            let named = data.binding_info.iter().flat_map(|(&set, bindings)| {
+                bindings
+                    .iter()
+                    .filter(|(_, info)| info.name == name)
+                    .map(move |(&binding, _)| (set, binding))
+            });
+            for (set, binding) in named {
+                let descriptor = match data.descriptions.get(&set).and_then(|s| s.get(&binding)) {
+                    Some(d) => d.clone(),
+                    None => continue,
+                };
+                found = Some(match found {
+                    Some(f) if (f.set, f.binding) == (set, binding) => NamedBinding {
+                        stages: f.stages | *stages,
+                        descriptor: f.descriptor.union(&descriptor).unwrap_or(f.descriptor),
+                        ..f
+                    },
+                    // Stages that disagree on where it is are left to `layout`
+                    Some(f) => f,
+                    None => NamedBinding {
+                        name: name.to_string(),
+                        stages: *stages,
+                        set,
+                        binding,
+                        descriptor,
+                    },
+                });
+            }
+        }
+        found
+    }
+
+    /// The name of the descriptor at a set and binding.
+    pub fn binding_name(&self, set: usize, binding: usize) -> Option<&str> {
+        self.stage_layouts()
+            .iter()
+            .filter_map(|(_, data)| data.binding_info.get(&set).and_then(|s| s.get(&binding)))
+            .map(|info| info.name.as_str())
+            .next()
+    }
+
+    fn stage_layouts(&self) -> [(ShaderStages, &LayoutData); 3] {
+        let none = ShaderStages::none();
+        [
+            (ShaderStages { vertex: true, ..none }, &self.vert_layout.layout_data),
+            (ShaderStages { fragment: true, ..none }, &self.frag_layout.layout_data),
+            (ShaderStages::compute(), &self.compute_layout.layout_data),
+        ]
+    }
+
+    fn stage_infos(&self) -> [&StageInfo; 3] {
+        [&self.vert_info, &self.frag_info, &self.compute_info]
+    }
+
+    /// The specialization constants of every stage, listed once each.
+    pub fn spec_constants(&self) -> Vec<SpecConstant> {
+        let mut constants: Vec<SpecConstant> = Vec::new();
+        let stages = [
+            &self.vert_layout.layout_data,
+            &self.frag_layout.layout_data,
+            &self.compute_layout.layout_data,
+        ];
+        for constant in stages.iter().flat_map(|data| &data.spec_constants) {
+            if !constants.contains(constant) {
+                constants.push(constant.clone());
+            }
+        }
+        constants
+    }
+}
+
+fn merge_layouts(mut merged: LayoutData, data: &LayoutData) -> Result<LayoutData, Error> {
+    for (&set, bindings) in &data.descriptions {
+        let merged_set = merged.descriptions.entry(set).or_default();
+        for (&binding, desc) in bindings {
+            let desc = match merged_set.remove(&binding) {
+                Some(first) => {
+                    if first.ty != desc.ty || first.array_count != desc.array_count {
+                        return Err(Error::Merge(MergeError::Descriptor {
+                            set,
+                            binding,
+                            first,
+                            second: desc.clone(),
+                        }));
+                    }
+                    // Types already match so the union can't fail
+                    first.union(desc).unwrap_or(first)
+                }
+                None => desc.clone(),
+            };
+            merged_set.insert(binding, desc);
+        }
+    }
+    for (&set, bindings) in &data.binding_info {
+        let merged_set = merged.binding_info.entry(set).or_default();
+        for (&binding, info) in bindings {
+            let merged_info = merged_set.entry(binding).or_insert_with(|| info.clone());
+            merged_info.variable_count |= info.variable_count;
+        }
+    }
+    for block in &data.push_constants {
+        if !merged.push_constants.contains(block) {
+            merged.push_constants.push(block.clone());
+        }
+    }
+    for constant in &data.spec_constants {
+        if !merged.spec_constants.contains(constant) {
+            merged.spec_constants.push(constant.clone());
+        }
+    }
+    merged.count_bindings();
+    merged.pc_ranges.extend(data.pc_ranges.iter().copied());
+    merged.pc_ranges = merge_pc_ranges(merged.pc_ranges);
+    merged.num_constants = merged.pc_ranges.len();
+    Ok(merged)
+}
+
+// Vulkan only allows a stage to appear in one push constant range so
+// overlapping ranges are joined and share the stages of both.
+fn merge_pc_ranges(mut ranges: Vec<PipelineLayoutDescPcRange>) -> Vec<PipelineLayoutDescPcRange> {
+    ranges.sort_by_key(|pc| pc.offset);
+    let mut merged: Vec<PipelineLayoutDescPcRange> = Vec::with_capacity(ranges.len());
+    for pc in ranges {
+        match merged.last_mut() {
+            Some(last) if pc.offset < last.offset + last.size => {
+                let end = (last.offset + last.size).max(pc.offset + pc.size);
+                last.size = end - last.offset;
+                last.stages = last.stages | pc.stages;
+            }
+            _ => merged.push(pc),
+        }
+    }
+    merged
+}
+
+/// What a single stage declares about itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StageInfo {
+    pub capabilities: Vec<Capability>,
+    /// SPIR-V extensions, like `SPV_KHR_multiview`
+    pub extensions: Vec<String>,
+    /// Built-ins the stage loads, like `gl_InstanceIndex` or `gl_SampleID`
+    pub built_ins_read: Vec<BuiltIn>,
+    /// Built-ins the stage stores to, like `gl_Position` or `gl_FragDepth`
+    pub built_ins_written: Vec<BuiltIn>,
+    /// Execution modes the entry point declares, like `early_fragment_tests`
+    pub execution_modes: ExecutionModes,
+}
+
+/// The execution modes of a stage that affect how a pipeline is set up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutionModes {
+    /// Depth and stencil tests run before the fragment shader, `early_fragment_tests`
+    pub early_fragment_tests: bool,
+    /// The fragment shader writes `gl_FragDepth`
+    pub depth_replacing: bool,
+    /// How any depth the fragment shader writes relates to the interpolated depth
+    pub depth: Option<DepthMode>,
+    /// `gl_FragCoord` has its origin at the upper left rather than the lower left
+    pub origin_upper_left: bool,
+    /// `gl_FragCoord` is at the integer corner of pixels rather than their
+    /// centre. Vulkan doesn't allow this so a usable module never sets it
+    pub pixel_center_integer: bool,
+    /// The vertex stage captures outputs with transform feedback
+    pub xfb: bool,
+}
+
+/// A `layout(depth_*)` qualifier on `gl_FragDepth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthMode {
+    /// `depth_greater`, the depth is only ever made larger
+    Greater,
+    /// `depth_less`, the depth is only ever made smaller
+    Less,
+    /// `depth_unchanged`, the depth is written back as it was
+    Unchanged,
+}
+
+impl StageInfo {
+    /// The device features the capabilities need.
+    pub fn required_features(&self) -> Features {
+        validation::required_features(&self.capabilities)
+    }
+
+    /// The device extensions the SPIR-V extensions and capabilities need.
+    pub fn required_extensions(&self) -> RawDeviceExtensions {
+        validation::required_extensions(&self.extensions, &self.capabilities)
+    }
+}
+
+/// Reflection details of a binding that `DescriptorDesc` has no room for.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BindingInfo {
+    /// The variable name, or the block name when the block has no instance name
+    pub name: String,
+    /// The shader declares an array without a size, so the
+    /// number of descriptors can vary with each set allocated
+    pub variable_count: bool,
+    /// The image is sampled with depth comparison, like `sampler2DShadow`
+    pub depth_comparison: bool,
+    /// The type an image gives when it's read. Not set for other descriptors
+    pub sampled_type: Option<SampledType>,
+    /// The members of a uniform or storage block
+    pub members: Vec<BlockMember>,
+}
+
+/// A descriptor found by name with `Entry::binding_by_name`.
+#[derive(Debug, Clone)]
+pub struct NamedBinding {
+    pub name: String,
+    pub stages: ShaderStages,
+    pub set: usize,
+    pub binding: usize,
+    pub descriptor: DescriptorDesc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampledType {
+    Float,
+    Sint,
+    Uint,
+}
+
+/// A push constant block as the shader declares it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushConstantBlock {
+    /// The instance name, like `pc` in `} pc;`
+    pub name: String,
+    /// The block name, like `PushConstantData`
+    pub type_name: String,
+    pub size: u32,
+    pub members: Vec<BlockMember>,
+}
+
+/// A member of a uniform, storage or push constant block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockMember {
+    pub name: String,
+    pub ty: MemberType,
+    /// Bytes from the start of the struct holding this member
+    pub offset: u32,
+    /// Size in bytes up to the next member, or 0 for runtime sized arrays
+    pub size: u32,
+    /// The length of each array dimension with 0 for a runtime sized array
+    pub array: Vec<u32>,
+    pub array_stride: u32,
+    pub matrix_stride: u32,
+    pub row_major: bool,
+    /// The members of a struct
+    pub members: Vec<BlockMember>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberType {
+    Scalar(ScalarType),
+    Vector(ScalarType, u32),
+    Matrix {
+        ty: ScalarType,
+        columns: u32,
+        rows: u32,
+    },
+    Struct,
+}
+
+/// A scalar type and its width in bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarType {
+    Bool,
+    Sint(u32),
+    Uint(u32),
+    Float(u32),
+}
+
+/// A specialization constant, like `layout(constant_id = 3) const int n = 8;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecConstant {
+    /// The `constant_id` pipelines specialize it by
+    pub id: u32,
+    pub name: String,
+    pub ty: ScalarType,
+    /// The value used when the pipeline doesn't specialize it
+    pub default: SpecValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecValue {
+    Bool(bool),
+    Sint(i64),
+    Uint(u64),
+    Float(f64),
+}
+
+/// Values for the specialization constants of a shader that can be
+/// passed to vulkano when creating a pipeline. Vulkano wants the layout
+/// of the values fixed at compile time, so each `constant_id` below
+/// `SpecConstants::SLOTS` gets a 4 byte slot. That covers booleans and
+/// 32 bit numbers, which is what GLSL declares most constants as.
+/// Constants with an id of 64 or more keep the default the shader gives
+/// them. Other sizes below that still get a 4 byte entry, which Vulkan
+/// doesn't allow, so pipelines for those shaders should be given `()`.
+#[derive(Clone)]
+#[repr(C)]
+pub struct SpecConstants {
+    values: [u32; SpecConstants::SLOTS],
+    constants: Vec<SpecConstant>,
+}
+
+impl SpecConstants {
+    pub const SLOTS: usize = 64;
+
+    /// Starts every constant at its default value. Constants without a
+    /// slot are skipped here and only setting them gives `Unsupported`.
+    pub fn new(constants: &[SpecConstant]) -> Result<Self, Error> {
+        let mut spec = SpecConstants {
+            values: [0; SpecConstants::SLOTS],
+            constants: constants.to_vec(),
+        };
+        for c in constants.iter().filter(|c| has_slot(c)) {
+            spec.set_id(c.id, c.default)?;
+        }
+        Ok(spec)
+    }
+
+    /// Sets the constant with this name. The value has to be of
+    /// the same kind as the constant and fit in its width.
+    pub fn set(&mut self, name: &str, value: SpecValue) -> Result<(), Error> {
+        let id = self
+            .constants
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.id)
+            .ok_or_else(|| Error::SpecConstant(SpecConstantError::Missing(name.to_string())))?;
+        self.set_id(id, value)
+    }
+
+    /// Same as `set` but finds the constant by its `constant_id`.
+    pub fn set_id(&mut self, id: u32, value: SpecValue) -> Result<(), Error> {
+        let constant = self
+            .constants
+            .iter()
+            .find(|c| c.id == id)
+            .ok_or(Error::SpecConstant(SpecConstantError::MissingId(id)))?;
+        let ty = constant.ty;
+        if !has_slot(constant) {
+            return Err(Error::SpecConstant(SpecConstantError::Unsupported { id, ty }));
+        }
+        let word = match (ty, value) {
+            (ScalarType::Bool, SpecValue::Bool(b)) => Some(b as u32),
+            (ScalarType::Sint(_), SpecValue::Sint(v)) => i32::try_from(v).ok().map(|v| v as u32),
+            (ScalarType::Uint(_), SpecValue::Uint(v)) => u32::try_from(v).ok(),
+            // Floats are rounded to 32 bits rather than rejected
+            (ScalarType::Float(_), SpecValue::Float(v)) => Some((v as f32).to_bits()),
+            _ => None,
+        };
+        self.values[id as usize] = word.ok_or_else(|| {
+            Error::SpecConstant(SpecConstantError::Type {
+                name: constant.name.clone(),
+                ty,
+                value,
+            })
+        })?;
+        Ok(())
+    }
+
+    /// The current value of a constant.
+    pub fn get(&self, name: &str) -> Option<SpecValue> {
+        let id = self.constants.iter().find(|c| c.name == name)?.id;
+        self.get_id(id)
+    }
+
+    /// Same as `get` but finds the constant by its `constant_id`.
+    pub fn get_id(&self, id: u32) -> Option<SpecValue> {
+        let constant = self.constants.iter().find(|c| c.id == id)?;
+        if !has_slot(constant) {
+            return Some(constant.default);
+        }
+        let word = self.values[id as usize];
+        Some(match constant.ty {
+            ScalarType::Bool => SpecValue::Bool(word != 0),
+            ScalarType::Sint(_) => SpecValue::Sint(word as i32 as i64),
+            ScalarType::Uint(_) => SpecValue::Uint(word as u64),
+            ScalarType::Float(_) => SpecValue::Float(f32::from_bits(word) as f64),
+        })
+    }
+}
+
+fn has_slot(constant: &SpecConstant) -> bool {
+    let fits = match constant.ty {
+        ScalarType::Bool => true,
+        ScalarType::Sint(w) | ScalarType::Uint(w) | ScalarType::Float(w) => w == 32,
+    };
+    fits && (constant.id as usize) < SpecConstants::SLOTS
+}
+
+impl std::fmt::Debug for SpecConstants {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.constants.iter().map(|c| (&c.name, self.get(&c.name))))
+            .finish()
+    }
+}
+
+// The entries only describe the `values` array, which is first in the
+// struct. Vulkan ignores entries for ids the shader doesn't declare.
+unsafe impl SpecializationConstants for SpecConstants {
+    fn descriptors() -> &'static [SpecializationMapEntry] {
+        const SLOT: SpecializationMapEntry = SpecializationMapEntry {
+            constant_id: 0,
+            offset: 0,
+            size: 4,
+        };
+        static DESCRIPTORS: [SpecializationMapEntry; SpecConstants::SLOTS] = {
+            let mut entries = [SLOT; SpecConstants::SLOTS];
+            let mut i = 0;
+            while i < SpecConstants::SLOTS {
+                entries[i].constant_id = i as u32;
+                entries[i].offset = i as u32 * 4;
+                i += 1;
+            }
+            entries
+        };
+        &DESCRIPTORS
+    }
+}
+
+/// The number of invocations in each workgroup of a compute shader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkgroupSize {
+    /// The size the shader was compiled with, in x, y and z
+    pub size: [u32; 3],
+    /// The `constant_id` of each dimension declared with `local_size_x_id` and friends
+    pub spec_ids: [Option<u32>; 3],
+}
+
+impl WorkgroupSize {
+    /// The size after any dimensions that come from spec constants are
+    /// given their values. Specializing a dimension with something other
+    /// than an unsigned 32 bit integer leaves it at its default.
+    pub fn specialized(&self, constants: &SpecConstants) -> [u32; 3] {
+        let mut size = self.size;
+        for (size, id) in size.iter_mut().zip(&self.spec_ids) {
+            let value = id.and_then(|id| constants.get_id(id));
+            if let Some(SpecValue::Uint(v)) = value {
+                *size = v as u32;
+            }
+        }
+        size
+    }
+
+    /// The number of workgroups to dispatch so that every one of
+    /// `invocations` is covered, rounding each dimension up.
+    pub fn dispatch(&self, invocations: [u32; 3]) -> [u32; 3] {
+        dispatch_size(self.size, invocations)
+    }
+
+    /// Same as `dispatch` for a specialized pipeline.
+    pub fn dispatch_specialized(&self, constants: &SpecConstants, invocations: [u32; 3]) -> [u32; 3] {
+        dispatch_size(self.specialized(constants), invocations)
+    }
+}
+
+fn dispatch_size(size: [u32; 3], invocations: [u32; 3]) -> [u32; 3] {
+    // Rounds up without the overflow of adding `size - 1` first
+    let groups = |i: usize| {
+        let size = size[i].max(1);
+        let whole = invocations[i] / size;
+        if whole * size < invocations[i] {
+            whole + 1
+        } else {
+            whole
+        }
+    };
+    [groups(0), groups(1), groups(2)]
+}
+
+/// The counts to give descriptor arrays that the shader leaves unsized,
+/// like the textures of a bindless renderer.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeArrays {
+    /// Used by every runtime array without a count of its own
+    pub max: Option<u32>,
+    /// Counts for particular `(set, binding)`s
+    pub counts: HashMap<(usize, usize), u32>,
+}
+
+impl RuntimeArrays {
+    /// Gives every runtime array up to `max` descriptors.
+    pub fn max(max: u32) -> Self {
+        RuntimeArrays {
+            max: Some(max),
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Sets the count of a single binding.
+    pub fn count_for(mut self, set: usize, binding: usize, count: u32) -> Self {
+        self.counts.insert((set, binding), count);
+        self
+    }
+
+    /// The count for a binding, if there is one.
+    pub fn count(&self, set: usize, binding: usize) -> Option<u32> {
+        self.counts.get(&(set, binding)).copied().or(self.max)
+    }
+}
+
+/// The layouts of all stages of an `Entry` merged into one.
+#[derive(Debug, Clone, Default)]
+pub struct EntryLayout {
+    pub layout_data: LayoutData,
+}
+
+unsafe impl PipelineLayoutDesc for EntryLayout {
+    fn num_sets(&self) -> usize {
+        self.layout_data.num_sets
+    }
+    fn num_bindings_in_set(&self, set: usize) -> Option<usize> {
+        self.layout_data.num_bindings.get(&set).copied()
+    }
+    fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
+        self.layout_data.descriptions.get(&set)
+            .and_then(|s|s.get(&binding))
+            .cloned()
+    }
+    fn num_push_constants_ranges(&self) -> usize {
+        self.layout_data.num_constants
+    }
+    fn push_constants_range(&self, num: usize) -> Option<PipelineLayoutDescPcRange> {
+        self.layout_data.pc_ranges.get(num).copied()
+    }
+}
+
+/// A single entry point of a SPIR-V module, reflected with only
+/// the interfaces and resources that it uses.
+#[derive(Debug, Clone)]
+pub struct EntryPoint {
+    pub name: String,
+    pub execution_model: ExecutionModel,
+    pub inputs: Vec<ShaderInterfaceDefEntry>,
+    pub outputs: Vec<ShaderInterfaceDefEntry>,
+    pub layout_data: LayoutData,
+    pub info: StageInfo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionModel {
+    Vertex,
+    TessellationControl,
+    TessellationEvaluation,
+    Geometry,
+    Fragment,
+    GLCompute,
+}
+
+impl ExecutionModel {
+    /// The shader stage this execution model runs in.
+    pub fn stages(self) -> ShaderStages {
+        let none = ShaderStages::none();
+        match self {
+            ExecutionModel::Vertex => ShaderStages { vertex: true, ..none },
+            ExecutionModel::TessellationControl => ShaderStages { tessellation_control: true, ..none },
+            ExecutionModel::TessellationEvaluation => ShaderStages { tessellation_evaluation: true, ..none },
+            ExecutionModel::Geometry => ShaderStages { geometry: true, ..none },
+            ExecutionModel::Fragment => ShaderStages { fragment: true, ..none },
+            ExecutionModel::GLCompute => ShaderStages::compute(),
+        }
+    }
+}
+
+/// Interpolation qualifiers of a vertex output or fragment input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Interpolation {
+    pub flat: bool,
+    pub no_perspective: bool,
+    pub centroid: bool,
+    pub sample: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -46,21 +706,19 @@ unsafe impl ShaderInterfaceDef for FragOutput {
 
 pub type FragOutputIter = std::vec::IntoIter<ShaderInterfaceDefEntry>;
 
+impl FragOutput {
+    /// Checks the outputs against the formats of the colour attachments
+    /// they are written to, in location order.
+    pub fn check_attachments(&self, attachments: &[Format]) -> Result<(), Error> {
+        validation::check_attachments(&self.outputs, attachments)
+    }
+}
+
 // Layout same as with vertex shader.
 #[derive(Debug, Clone, Default)]
 pub struct FragLayout {
     pub layout_data: LayoutData,
 }
-impl FragLayout {
-    const STAGES: ShaderStages = ShaderStages {
-     vertex: false,
-     tessellation_control: false,
-     tessellation_evaluation: false,
-     geometry: false,
-     fragment: true,
-     compute: false,
-    };
-}
 unsafe impl PipelineLayoutDesc for FragLayout {
     fn num_sets(&self) -> usize {
         self.layout_data.num_sets
@@ -71,24 +729,13 @@ unsafe impl PipelineLayoutDesc for FragLayout {
     fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
         self.layout_data.descriptions.get(&set)
             .and_then(|s|s.get(&binding))
-            .map(|desc| {
-                let mut desc = desc.clone();
-                desc.stages = FragLayout::STAGES;
-                desc
-            })
-
+            .cloned()
     }
     fn num_push_constants_ranges(&self) -> usize {
         self.layout_data.num_constants
     }
     fn push_constants_range(&self, num: usize) -> Option<PipelineLayoutDescPcRange> {
-        self.layout_data.pc_ranges.get(num)
-            .map(|desc| {
-                let mut desc = *desc;
-                desc.stages = FragLayout::STAGES;
-                desc
-            })
-
+        self.layout_data.pc_ranges.get(num).copied()
     }
 }
 
@@ -107,6 +754,44 @@ unsafe impl ShaderInterfaceDef for VertInput {
 
 pub type VertInputIter = std::vec::IntoIter<ShaderInterfaceDefEntry>;
 
+impl VertInput {
+    /// Replaces the format of the input at `location` with the format the
+    /// vertex buffer actually holds, like `R8G8B8A8Unorm` feeding a `vec4`.
+    /// Inputs spanning several locations are split around it.
+    pub fn override_format(&mut self, location: u32, format: Format) -> Result<(), Error> {
+        let index = self
+            .inputs
+            .iter()
+            .position(|i| i.location.contains(&location))
+            .ok_or(Error::Override(OverrideError::Missing { location }))?;
+        let input = self.inputs.remove(index);
+        if !validation::can_read_as(format, input.format) {
+            let shader = input.format;
+            self.inputs.insert(index, input);
+            return Err(Error::Override(OverrideError::Incompatible {
+                location,
+                shader,
+                format,
+            }));
+        }
+        let split = [
+            (input.location.start..location, input.format),
+            (location..(location + 1), format),
+            ((location + 1)..input.location.end, input.format),
+        ];
+        let entries = split
+            .iter()
+            .filter(|(range, _)| !range.is_empty())
+            .map(|(range, format)| ShaderInterfaceDefEntry {
+                location: range.clone(),
+                format: *format,
+                name: input.name.clone(),
+            });
+        self.inputs.splice(index..index, entries);
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct VertOutput {
     pub outputs: Vec<ShaderInterfaceDefEntry>,
@@ -127,16 +812,6 @@ pub type VertOutputIter = std::vec::IntoIter<ShaderInterfaceDefEntry>;
 pub struct VertLayout {
     pub layout_data: LayoutData,
 }
-impl VertLayout {
-    const STAGES: ShaderStages = ShaderStages {
-     vertex: true,
-     tessellation_control: false,
-     tessellation_evaluation: false,
-     geometry: false,
-     fragment: false,
-     compute: false,
-    };
-}
 unsafe impl PipelineLayoutDesc for VertLayout {
     fn num_sets(&self) -> usize {
         self.layout_data.num_sets
@@ -147,24 +822,13 @@ unsafe impl PipelineLayoutDesc for VertLayout {
     fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
         self.layout_data.descriptions.get(&set)
             .and_then(|s|s.get(&binding))
-            .map(|desc| {
-                let mut desc = desc.clone();
-                desc.stages = VertLayout::STAGES;
-                desc
-            })
-
+            .cloned()
     }
     fn num_push_constants_ranges(&self) -> usize {
         self.layout_data.num_constants
     }
     fn push_constants_range(&self, num: usize) -> Option<PipelineLayoutDescPcRange> {
-        self.layout_data.pc_ranges.get(num)
-            .map(|desc| {
-                let mut desc = *desc;
-                desc.stages = VertLayout::STAGES;
-                desc
-            })
-
+        self.layout_data.pc_ranges.get(num).copied()
     }
 }
 
@@ -173,17 +837,6 @@ pub struct ComputeLayout {
     pub layout_data: LayoutData,
 }
 
-impl ComputeLayout {
-    const STAGES: ShaderStages = ShaderStages {
-     vertex: false,
-     tessellation_control: false,
-     tessellation_evaluation: false,
-     geometry: false,
-     fragment: false,
-     compute: true,
-    };
-}
-
 unsafe impl PipelineLayoutDesc for ComputeLayout {
     fn num_sets(&self) -> usize {
         self.layout_data.num_sets
@@ -194,23 +847,12 @@ unsafe impl PipelineLayoutDesc for ComputeLayout {
     fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
         self.layout_data.descriptions.get(&set)
             .and_then(|s|s.get(&binding))
-            .map(|desc| {
-                let mut desc = desc.clone();
-                desc.stages = Self::STAGES;
-                desc
-            })
-
+            .cloned()
     }
     fn num_push_constants_ranges(&self) -> usize {
         self.layout_data.num_constants
     }
     fn push_constants_range(&self, num: usize) -> Option<PipelineLayoutDescPcRange> {
-        self.layout_data.pc_ranges.get(num)
-            .map(|desc| {
-                let mut desc = *desc;
-                desc.stages = Self::STAGES;
-                desc
-            })
-
+        self.layout_data.pc_ranges.get(num).copied()
     }
 }
@@ -1,16 +1,22 @@
+mod backend;
 mod compiler;
 pub mod error;
 mod reflection;
+mod spirv;
 mod srvk;
+mod stats;
+mod validation;
 pub mod layouts;
+#[cfg(any(test, feature = "native-reflect", not(feature = "spirv-reflect")))]
+mod native;
 mod watch;
 
 pub use layouts::*;
 pub use reflection::LayoutData;
-pub use watch::{Message, Watch};
+pub use stats::{Budget, Limit, OpcodeClasses, ShaderStats, Stage, Statistics};
+pub use watch::{Checks, Message, Watch};
 pub use error::*;
 
-use spirv_reflect as sr;
 use vulkano as vk;
 use std::path::Path;
 use shaderc::ShaderKind;
@@ -42,10 +48,46 @@ where
 }
 
 pub fn parse_compute(code: &CompiledShaders) -> Result<Entry, Error> {
-    reflection::create_compute_entry(code)
+    reflection::create_compute_entry(code, &RuntimeArrays::default())
 }
 
 /// Parses the shaders and gives an entry point
 pub fn parse(code: &CompiledShaders) -> Result<Entry, Error> {
-    reflection::create_entry(code)
+    reflection::create_entry(code, &RuntimeArrays::default())
+}
+
+/// Same as `parse` but runtime sized descriptor
+/// arrays get their counts from `arrays`
+pub fn parse_with_arrays(code: &CompiledShaders, arrays: &RuntimeArrays) -> Result<Entry, Error> {
+    reflection::create_entry(code, arrays)
+}
+
+/// Same as `parse_compute` but runtime sized descriptor
+/// arrays get their counts from `arrays`
+pub fn parse_compute_with_arrays(code: &CompiledShaders, arrays: &RuntimeArrays) -> Result<Entry, Error> {
+    reflection::create_compute_entry(code, arrays)
+}
+
+/// Parses a single module that holds both the vertex and
+/// fragment stages under the given entry point names
+pub fn parse_module(code: &[u32], vertex: &str, fragment: &str) -> Result<Entry, Error> {
+    reflection::create_module_entry(code, vertex, fragment, &RuntimeArrays::default())
+}
+
+/// Same as `parse_module` but runtime sized descriptor
+/// arrays get their counts from `arrays`
+pub fn parse_module_with_arrays(
+    code: &[u32],
+    vertex: &str,
+    fragment: &str,
+    arrays: &RuntimeArrays,
+) -> Result<Entry, Error> {
+    reflection::create_module_entry(code, vertex, fragment, arrays)
+}
+
+/// Lists every entry point in a module along with the
+/// interfaces and resources each one uses. Runtime sized
+/// arrays are left for `LayoutData::resolve_runtime_arrays`
+pub fn parse_entry_points(code: &[u32]) -> Result<Vec<EntryPoint>, Error> {
+    reflection::create_entry_points(code)
 }
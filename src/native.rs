@@ -0,0 +1,622 @@
+// Reflection straight from the SPIR-V words, used in place of
+// spirv-reflect with the `native-reflect` feature or when spirv-reflect
+// is turned off. It works out block member sizes the way spirv-reflect
+// does and the `backends_agree` test in `backend.rs` checks that both
+// give the same results for the test shaders.
+use crate::backend::{
+    BlockVariable, DescriptorBinding, DescriptorSet, DescriptorType, EntryPointInfo, ImageTraits,
+    InterfaceVariable,
+};
+use crate::error::{Error, ReflectError};
+use crate::layouts::{BlockMember, ExecutionModel, MemberType, ScalarType};
+use crate::spirv;
+use num_traits::FromPrimitive;
+use spirv_headers as spv;
+use std::collections::HashSet;
+
+pub struct ShaderModule {
+    module: spirv::Module,
+}
+
+struct EntryPointDecl<'a> {
+    name: String,
+    model: u32,
+    interface: &'a [u32],
+}
+
+// A type with the vectors, matrices, arrays and pointers around it
+// folded into one description, the way spirv-reflect describes types.
+#[derive(Default)]
+struct Type {
+    id: u32,
+    // The innermost named type gives the name
+    name: String,
+    member_name: String,
+    scalar: Option<ScalarType>,
+    components: u32,
+    columns: u32,
+    matrix_stride: u32,
+    // Only fixed size arrays add dimensions
+    dims: Vec<u32>,
+    // Nested arrays leave the innermost stride
+    array_stride: u32,
+    image: Option<ImageTraits>,
+    // The image's sampled operand, 1 for sampled and 2 for storage
+    image_sampled: u32,
+    sampled_image: bool,
+    sampler: bool,
+    is_struct: bool,
+    block: bool,
+    buffer_block: bool,
+    members: Vec<Type>,
+}
+
+impl Type {
+    fn member_type(&self) -> MemberType {
+        let scalar = self.scalar.unwrap_or(ScalarType::Uint(0));
+        if self.is_struct {
+            MemberType::Struct
+        } else if self.columns > 0 {
+            MemberType::Matrix {
+                ty: scalar,
+                columns: self.columns,
+                rows: self.components,
+            }
+        } else if self.components > 0 {
+            MemberType::Vector(scalar, self.components)
+        } else {
+            MemberType::Scalar(scalar)
+        }
+    }
+}
+
+// A block member along with what its size is worked out from
+struct Member {
+    op: Option<spv::Op>,
+    width: u32,
+    column_major: bool,
+    is_struct: bool,
+    // `block.members` is filled in from `members` once they're sized
+    block: BlockMember,
+    members: Vec<Member>,
+}
+
+impl ShaderModule {
+    pub fn load(data: &[u32]) -> Result<Self, Error> {
+        spirv::Module::new(data).map(|module| ShaderModule { module })
+    }
+
+    // Tests also build this reader next to spirv-reflect
+    #[cfg(any(feature = "native-reflect", not(feature = "spirv-reflect")))]
+    pub fn module(&self) -> &spirv::Module {
+        &self.module
+    }
+
+    pub fn entry_points(&self) -> Result<Vec<EntryPointInfo>, Error> {
+        Ok(self
+            .entry_point_decls()
+            .map(|ep| EntryPointInfo {
+                execution_model: execution_model(ep.model),
+                name: ep.name,
+            })
+            .collect())
+    }
+
+    /// The stage of the first entry point
+    pub fn execution_model(&self) -> Option<ExecutionModel> {
+        self.entry_point_decls()
+            .next()
+            .and_then(|ep| execution_model(ep.model))
+    }
+
+    pub fn input_variables(
+        &self,
+        entry_point: Option<&str>,
+    ) -> Result<Vec<InterfaceVariable>, Error> {
+        self.interface_variables(entry_point, spv::StorageClass::Input)
+    }
+
+    pub fn output_variables(
+        &self,
+        entry_point: Option<&str>,
+    ) -> Result<Vec<InterfaceVariable>, Error> {
+        self.interface_variables(entry_point, spv::StorageClass::Output)
+    }
+
+    /// Sets in order with their bindings sorted by number. With an entry
+    /// point only the bindings it uses are listed.
+    pub fn descriptor_sets(&self, entry_point: Option<&str>) -> Result<Vec<DescriptorSet>, Error> {
+        let used = self.used_ids(entry_point)?;
+        let mut bindings = self
+            .variables()
+            .filter(|v| {
+                let class = v.operands[2];
+                class == spv::StorageClass::Uniform as u32
+                    || class == spv::StorageClass::UniformConstant as u32
+                    || class == spv::StorageClass::StorageBuffer as u32
+            })
+            .filter(|v| {
+                used.as_ref()
+                    .map(|u| u.contains(&v.operands[1]))
+                    .unwrap_or(true)
+            })
+            .filter_map(|v| {
+                let id = v.operands[1];
+                let set = self.module.decoration(id, spv::Decoration::DescriptorSet)?;
+                let binding = self.module.decoration(id, spv::Decoration::Binding)?;
+                let storage = v.operands[2] == spv::StorageClass::StorageBuffer as u32;
+                Some(
+                    self.descriptor_binding(id, v.operands[0], binding, storage)
+                        .map(|b| (set, b)),
+                )
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        bindings.sort_by_key(|(set, b)| (*set, b.binding, b.spirv_id));
+        let mut sets: Vec<DescriptorSet> = Vec::new();
+        for (set, binding) in bindings {
+            match sets.last_mut() {
+                Some(last) if last.set == set => last.bindings.push(binding),
+                _ => sets.push(DescriptorSet {
+                    set,
+                    bindings: vec![binding],
+                }),
+            }
+        }
+        Ok(sets)
+    }
+
+    /// Blocks in the order they're declared. With an entry point
+    /// only the blocks it uses are listed.
+    pub fn push_constant_blocks(
+        &self,
+        entry_point: Option<&str>,
+    ) -> Result<Vec<BlockVariable>, Error> {
+        let used = self.used_ids(entry_point)?;
+        Ok(self
+            .variables()
+            .filter(|v| v.operands[2] == spv::StorageClass::PushConstant as u32)
+            .filter(|v| {
+                used.as_ref()
+                    .map(|u| u.contains(&v.operands[1]))
+                    .unwrap_or(true)
+            })
+            .map(|v| {
+                let ty = self.describe(self.pointee(v.operands[0]), None);
+                let mut members = self.block_members(&ty);
+                BlockVariable {
+                    spirv_id: v.operands[1],
+                    type_name: ty.name,
+                    size: block_sizes(&mut members, false),
+                    members: into_block_members(members),
+                }
+            })
+            .collect())
+    }
+
+    fn entry_point_decls(&self) -> impl Iterator<Item = EntryPointDecl<'_>> {
+        self.module
+            .instructions
+            .iter()
+            .filter(|i| i.op == spv::Op::EntryPoint)
+            .map(|i| {
+                let (name, len) = spirv::string(&i.operands[2..]);
+                EntryPointDecl {
+                    name,
+                    model: i.operands[0],
+                    interface: i.operands.get(2 + len..).unwrap_or(&[]),
+                }
+            })
+    }
+
+    // Without a name this is the first entry point
+    fn entry_point(&self, entry_point: Option<&str>) -> Result<EntryPointDecl<'_>, Error> {
+        let mut decls = self.entry_point_decls();
+        match entry_point {
+            Some(name) => decls
+                .find(|ep| ep.name == name)
+                .ok_or_else(|| Error::EntryPoint(format!("no entry point named {}", name))),
+            None => decls
+                .next()
+                .ok_or_else(|| Error::EntryPoint("module has no entry points".to_string())),
+        }
+    }
+
+    // The ids an entry point's code refers to, or `None`
+    // for the whole module.
+    fn used_ids(&self, entry_point: Option<&str>) -> Result<Option<HashSet<u32>>, Error> {
+        match entry_point {
+            Some(name) => {
+                self.entry_point(Some(name))?;
+                Ok(Some(self.module.referenced_ids(Some(name))))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn variables(&self) -> impl Iterator<Item = &spirv::Instruction> {
+        self.module
+            .instructions
+            .iter()
+            .filter(|i| i.op == spv::Op::Variable)
+    }
+
+    fn op(&self, id: u32) -> Option<spv::Op> {
+        self.module.type_declaration(id).map(|t| t.op)
+    }
+
+    // The type a pointer type points to
+    fn pointee(&self, pointer: u32) -> u32 {
+        self.module
+            .type_declaration(pointer)
+            .filter(|t| t.op == spv::Op::TypePointer)
+            .map(|t| t.operands[2])
+            .unwrap_or(pointer)
+    }
+
+    fn interface_variables(
+        &self,
+        entry_point: Option<&str>,
+        class: spv::StorageClass,
+    ) -> Result<Vec<InterfaceVariable>, Error> {
+        let ep = self.entry_point(entry_point)?;
+        ep.interface
+            .iter()
+            .filter_map(|&id| self.module.variable(id))
+            .filter(|v| v.operands[2] == class as u32)
+            .map(|v| {
+                let id = v.operands[1];
+                let ty = self.describe(self.pointee(v.operands[0]), None);
+                let mut var = self.interface_variable(&ty, None)?;
+                var.spirv_id = id;
+                var.name = self.module.name(id).unwrap_or_default();
+                var.location = self
+                    .module
+                    .decoration(id, spv::Decoration::Location)
+                    .unwrap_or(u32::MAX);
+                var.built_in |= self.module.has_decoration(id, spv::Decoration::BuiltIn);
+                Ok(var)
+            })
+            .collect()
+    }
+
+    // `member` is the struct and index this is a member of
+    fn interface_variable(
+        &self,
+        ty: &Type,
+        member: Option<(u32, u32)>,
+    ) -> Result<InterfaceVariable, Error> {
+        if ty.scalar.is_none() && !ty.is_struct {
+            return Err(Error::Reflect(ReflectError::InterfaceType { id: ty.id }));
+        }
+        let members = ty
+            .members
+            .iter()
+            .enumerate()
+            .map(|(i, m)| self.interface_variable(m, Some((self.struct_type(ty.id), i as u32))))
+            .collect::<Result<Vec<_>, _>>()?;
+        let built_in = match member {
+            Some((id, index)) => {
+                self.module
+                    .has_member_decoration(id, index, spv::Decoration::BuiltIn)
+            }
+            None => false,
+        };
+        Ok(InterfaceVariable {
+            spirv_id: 0,
+            name: self.module.name(ty.id).unwrap_or_default(),
+            member_name: ty.member_name.clone(),
+            location: 0,
+            built_in: built_in || members.iter().any(|m| m.built_in),
+            ty: ty.member_type(),
+            array: if self.op(ty.id) == Some(spv::Op::TypeArray) {
+                ty.dims.clone()
+            } else {
+                Vec::new()
+            },
+            members,
+        })
+    }
+
+    fn descriptor_binding(
+        &self,
+        id: u32,
+        pointer: u32,
+        binding: u32,
+        storage: bool,
+    ) -> Result<DescriptorBinding, Error> {
+        use DescriptorType as D;
+        let ty = self.describe(self.pointee(pointer), None);
+        let texel_buffer = |sampled| match sampled {
+            1 => Some(D::UniformTexelBuffer),
+            2 => Some(D::StorageTexelBuffer),
+            _ => None,
+        };
+        let descriptor_type = match ty.image {
+            Some(image) if ty.sampled_image => match image.dim {
+                spv::Dim::DimBuffer => texel_buffer(ty.image_sampled),
+                _ => Some(D::CombinedImageSampler),
+            },
+            Some(image) => match (image.dim, ty.image_sampled) {
+                (spv::Dim::DimBuffer, sampled) => texel_buffer(sampled),
+                (spv::Dim::DimSubpassData, _) => Some(D::InputAttachment),
+                (_, 1) => Some(D::SampledImage),
+                (_, 2) => Some(D::StorageImage),
+                _ => None,
+            },
+            None if ty.sampler => Some(D::Sampler),
+            None if ty.is_struct && (storage || ty.buffer_block) => Some(D::StorageBuffer),
+            None if ty.is_struct && ty.block => Some(D::UniformBuffer),
+            None => None,
+        }
+        .ok_or(Error::Reflect(ReflectError::DescriptorType { id }))?;
+        let members = match descriptor_type {
+            D::UniformBuffer | D::StorageBuffer => {
+                let mut members = self.block_members(&ty);
+                // Storage buffers can end in a runtime array so their members aren't padded
+                block_sizes(&mut members, descriptor_type == D::StorageBuffer);
+                into_block_members(members)
+            }
+            _ => Vec::new(),
+        };
+        Ok(DescriptorBinding {
+            spirv_id: id,
+            name: self.module.name(id).unwrap_or_default(),
+            type_name: ty.name,
+            binding,
+            descriptor_type,
+            image: ty.image,
+            count: ty.dims.iter().product(),
+            members,
+        })
+    }
+
+    // The struct inside any arrays around it
+    fn struct_type(&self, mut id: u32) -> u32 {
+        while let Some(ty) = self.module.type_declaration(id) {
+            match ty.op {
+                spv::Op::TypeArray | spv::Op::TypeRuntimeArray => id = ty.operands[1],
+                _ => break,
+            }
+        }
+        id
+    }
+
+    // The members of a block with their offsets and
+    // decorations. Sizes are filled in by `block_sizes`.
+    fn block_members(&self, ty: &Type) -> Vec<Member> {
+        let struct_id = self.struct_type(ty.id);
+        ty.members
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let i = i as u32;
+                let op = self.op(m.id);
+                let (array, array_stride) = match op {
+                    Some(spv::Op::TypeArray) => (m.dims.clone(), m.array_stride),
+                    Some(spv::Op::TypeRuntimeArray) => {
+                        let stride = self.module.decoration(m.id, spv::Decoration::ArrayStride);
+                        (vec![0], stride.unwrap_or(0))
+                    }
+                    _ => (Vec::new(), 0),
+                };
+                let width = match m.scalar {
+                    Some(ScalarType::Sint(w))
+                    | Some(ScalarType::Uint(w))
+                    | Some(ScalarType::Float(w)) => w,
+                    _ => 0,
+                };
+                let decorated = |d| self.module.has_member_decoration(struct_id, i, d);
+                Member {
+                    op,
+                    width,
+                    column_major: decorated(spv::Decoration::ColMajor),
+                    is_struct: m.is_struct,
+                    block: BlockMember {
+                        name: self.module.member_name(struct_id, i).unwrap_or_default(),
+                        ty: m.member_type(),
+                        offset: self
+                            .module
+                            .member_decoration(struct_id, i, spv::Decoration::Offset)
+                            .unwrap_or(0),
+                        size: 0,
+                        array,
+                        array_stride,
+                        matrix_stride: m.matrix_stride,
+                        row_major: decorated(spv::Decoration::RowMajor),
+                        members: Vec::new(),
+                    },
+                    members: if m.is_struct {
+                        self.block_members(m)
+                    } else {
+                        Vec::new()
+                    },
+                }
+            })
+            .collect()
+    }
+
+    // Describes a type the way spirv-reflect does. Vectors, matrices,
+    // arrays and pointers are folded into a single description along
+    // with the type they hold.
+    fn describe(&self, id: u32, member: Option<(u32, u32)>) -> Type {
+        let mut ty = Type {
+            id,
+            ..Type::default()
+        };
+        self.fold_type(id, member, &mut ty);
+        ty
+    }
+
+    fn fold_type(&self, id: u32, member: Option<(u32, u32)>, ty: &mut Type) {
+        let decl = match self.module.type_declaration(id) {
+            Some(decl) => decl,
+            None => return,
+        };
+        let operands = &decl.operands;
+        ty.block |= self.module.has_decoration(id, spv::Decoration::Block);
+        ty.buffer_block |= self.module.has_decoration(id, spv::Decoration::BufferBlock);
+        match decl.op {
+            spv::Op::TypeBool => ty.scalar = Some(ScalarType::Bool),
+            spv::Op::TypeInt if operands[2] != 0 => ty.scalar = Some(ScalarType::Sint(operands[1])),
+            spv::Op::TypeInt => ty.scalar = Some(ScalarType::Uint(operands[1])),
+            spv::Op::TypeFloat => ty.scalar = Some(ScalarType::Float(operands[1])),
+            spv::Op::TypeVector => {
+                ty.components = operands[2];
+                self.fold_type(operands[1], None, ty);
+            }
+            spv::Op::TypeMatrix => {
+                ty.columns = operands[2];
+                // The stride is decorated on the member holding the matrix
+                ty.matrix_stride = match member {
+                    Some((s, m)) => {
+                        self.module
+                            .member_decoration(s, m, spv::Decoration::MatrixStride)
+                    }
+                    None => self.module.decoration(id, spv::Decoration::MatrixStride),
+                }
+                .unwrap_or(0);
+                self.fold_type(operands[1], None, ty);
+            }
+            spv::Op::TypeImage => {
+                ty.image = spv::Dim::from_u32(operands[2]).map(|dim| ImageTraits {
+                    dim,
+                    arrayed: operands[4] != 0,
+                    multisampled: operands[5] != 0,
+                    sampled: operands[6] == 1,
+                    format: spv::ImageFormat::from_u32(operands[7])
+                        .unwrap_or(spv::ImageFormat::Unknown),
+                });
+                ty.image_sampled = operands[6];
+            }
+            spv::Op::TypeSampler => ty.sampler = true,
+            spv::Op::TypeSampledImage => {
+                ty.sampled_image = true;
+                self.fold_type(operands[1], None, ty);
+            }
+            spv::Op::TypeArray => {
+                ty.array_stride = self
+                    .module
+                    .decoration(id, spv::Decoration::ArrayStride)
+                    .unwrap_or(0);
+                let length = self
+                    .module
+                    .constant_declaration(operands[2])
+                    .and_then(|c| c.operands.get(2).copied())
+                    .unwrap_or(0);
+                ty.dims.push(length);
+                self.fold_type(operands[1], None, ty);
+            }
+            spv::Op::TypeRuntimeArray => self.fold_type(operands[1], None, ty),
+            spv::Op::TypeStruct => {
+                ty.is_struct = true;
+                ty.members = operands[1..]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &member_type)| {
+                        let mut m = self.describe(member_type, Some((id, i as u32)));
+                        m.member_name = self.module.member_name(id, i as u32).unwrap_or_default();
+                        m
+                    })
+                    .collect();
+            }
+            spv::Op::TypePointer => self.fold_type(operands[2], None, ty),
+            _ => (),
+        }
+        if ty.name.is_empty() {
+            ty.name = self.module.name(id).unwrap_or_default();
+        }
+    }
+}
+
+// spirv-reflect works out member sizes from their types and then clamps
+// each to the gap before the next member. The last member is padded
+// out to 16 bytes and the block's size, which this returns, is the end
+// of that padding. Inside a runtime array (or a storage buffer) members
+// aren't padded.
+fn block_sizes(members: &mut [Member], runtime_array: bool) -> u32 {
+    if members.is_empty() {
+        return 0;
+    }
+    for m in members.iter_mut() {
+        let b = &mut m.block;
+        match m.op {
+            Some(spv::Op::TypeBool) => b.size = 4,
+            Some(spv::Op::TypeInt) | Some(spv::Op::TypeFloat) => b.size = m.width / 8,
+            Some(spv::Op::TypeVector) => {
+                if let MemberType::Vector(_, components) = b.ty {
+                    b.size = components * (m.width / 8);
+                }
+            }
+            Some(spv::Op::TypeMatrix) => {
+                if let MemberType::Matrix { columns, rows, .. } = b.ty {
+                    if m.column_major {
+                        b.size = columns * b.matrix_stride;
+                    } else if b.row_major {
+                        b.size = rows * b.matrix_stride;
+                    }
+                }
+            }
+            Some(spv::Op::TypeArray) => {
+                if m.is_struct {
+                    block_sizes(&mut m.members, runtime_array);
+                }
+                let elements = if b.array.is_empty() {
+                    0
+                } else {
+                    b.array.iter().product()
+                };
+                b.size = elements * b.array_stride;
+            }
+            Some(spv::Op::TypeRuntimeArray) if m.is_struct => {
+                b.size = block_sizes(&mut m.members, true)
+            }
+            Some(spv::Op::TypeStruct) => b.size = block_sizes(&mut m.members, runtime_array),
+            _ => (),
+        }
+    }
+    let mut padded_size = 0;
+    for i in 0..members.len() {
+        let end = match members.get(i + 1) {
+            Some(next) => next.block.offset,
+            None => {
+                let m = &members[i].block;
+                round_up(m.offset.wrapping_add(m.size), 16)
+            }
+        };
+        let m = &mut members[i].block;
+        padded_size = end.wrapping_sub(m.offset);
+        m.size = m.size.min(padded_size);
+        if runtime_array {
+            padded_size = m.size;
+        }
+    }
+    let last = &members[members.len() - 1].block;
+    last.offset.wrapping_add(padded_size)
+}
+
+fn into_block_members(members: Vec<Member>) -> Vec<BlockMember> {
+    members
+        .into_iter()
+        .map(|m| BlockMember {
+            members: into_block_members(m.members),
+            ..m.block
+        })
+        .collect()
+}
+
+fn round_up(value: u32, multiple: u32) -> u32 {
+    value.wrapping_add(multiple - 1) / multiple * multiple
+}
+
+fn execution_model(model: u32) -> Option<ExecutionModel> {
+    use spv::ExecutionModel as EM;
+    match EM::from_u32(model)? {
+        EM::Vertex => Some(ExecutionModel::Vertex),
+        EM::TessellationControl => Some(ExecutionModel::TessellationControl),
+        EM::TessellationEvaluation => Some(ExecutionModel::TessellationEvaluation),
+        EM::Geometry => Some(ExecutionModel::Geometry),
+        EM::Fragment => Some(ExecutionModel::Fragment),
+        EM::GLCompute => Some(ExecutionModel::GLCompute),
+        _ => None,
+    }
+}
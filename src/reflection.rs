@@ -1,11 +1,16 @@
-use crate::error::Error;
+use crate::backend::{self, InterfaceVariable};
+use crate::error::{ConvertError, Error, MergeError};
 use crate::layouts::*;
-use crate::sr;
+use crate::spirv;
 use crate::srvk::{DescriptorDescInfo, SpirvTy};
+use crate::validation;
 use crate::vk::descriptor::descriptor::*;
 use crate::vk::descriptor::pipeline_layout::PipelineLayoutDescPcRange;
+use crate::vk::format::{Format, FormatTy};
 use crate::vk::pipeline::shader::ShaderInterfaceDefEntry;
 use crate::CompiledShaders;
+use num_traits::FromPrimitive;
+use spirv_headers as spv;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -22,13 +27,90 @@ pub struct LayoutData {
     pub descriptions: HashMap<usize, HashMap<usize, DescriptorDesc>>,
     pub num_constants: usize,
     pub pc_ranges: Vec<PipelineLayoutDescPcRange>,
+    pub binding_info: HashMap<usize, HashMap<usize, BindingInfo>>,
+    pub push_constants: Vec<PushConstantBlock>,
+    pub spec_constants: Vec<SpecConstant>,
 }
 
-pub fn create_entry(shaders: &CompiledShaders) -> Result<Entry, Error> {
-    let vertex_interfaces = create_interfaces(&shaders.vertex)?;
-    let vertex_layout = create_layouts(&shaders.vertex)?;
-    let fragment_interfaces = create_interfaces(&shaders.fragment)?;
-    let fragment_layout = create_layouts(&shaders.fragment)?;
+impl LayoutData {
+    // Vulkan numbers sets and bindings from 0 so any gaps
+    // are counted as empty sets and empty bindings.
+    pub(crate) fn count_bindings(&mut self) {
+        self.num_sets = self
+            .descriptions
+            .keys()
+            .map(|&set| set + 1)
+            .max()
+            .unwrap_or(0);
+        let descriptions = &self.descriptions;
+        self.num_bindings = (0..self.num_sets)
+            .map(|set| {
+                let bindings = descriptions
+                    .get(&set)
+                    .and_then(|b| b.keys().map(|&binding| binding + 1).max())
+                    .unwrap_or(0);
+                (set, bindings)
+            })
+            .collect();
+    }
+
+    /// Gives every runtime sized descriptor array its count. Until this
+    /// is called they have an `array_count` of 0 so nothing can be bound.
+    pub fn resolve_runtime_arrays(&mut self, arrays: &RuntimeArrays) -> Result<(), Error> {
+        for (&set, bindings) in &self.binding_info {
+            for (&binding, info) in bindings {
+                if !info.variable_count {
+                    continue;
+                }
+                let count = arrays
+                    .count(set, binding)
+                    .ok_or(Error::RuntimeArray { set, binding })?;
+                if let Some(desc) = self
+                    .descriptions
+                    .get_mut(&set)
+                    .and_then(|s| s.get_mut(&binding))
+                {
+                    desc.array_count = count;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn create_entry(shaders: &CompiledShaders, arrays: &RuntimeArrays) -> Result<Entry, Error> {
+    let vertex = backend::ShaderModule::load(&shaders.vertex)?;
+    let fragment = backend::ShaderModule::load(&shaders.fragment)?;
+    create_graphics_entry((&vertex, None), (&fragment, None), arrays)
+}
+
+pub fn create_module_entry(
+    data: &[u32],
+    vertex: &str,
+    fragment: &str,
+    arrays: &RuntimeArrays,
+) -> Result<Entry, Error> {
+    let m = backend::ShaderModule::load(data)?;
+    find_entry_point(&m, vertex, ExecutionModel::Vertex)?;
+    find_entry_point(&m, fragment, ExecutionModel::Fragment)?;
+    create_graphics_entry((&m, Some(vertex)), (&m, Some(fragment)), arrays)
+}
+
+fn create_graphics_entry(
+    (vertex, vertex_name): (&backend::ShaderModule, Option<&str>),
+    (fragment, fragment_name): (&backend::ShaderModule, Option<&str>),
+    arrays: &RuntimeArrays,
+) -> Result<Entry, Error> {
+    check_push_constants((vertex, vertex_name), (fragment, fragment_name))?;
+    let warnings = validation::check_interface((vertex, vertex_name), (fragment, fragment_name))?;
+    let vert_info = create_stage_info(vertex.module(), vertex_name);
+    let frag_info = create_stage_info(fragment.module(), fragment_name);
+    let vertex_interfaces = create_interfaces(vertex, vertex_name)?;
+    let mut vertex_layout = create_layouts(vertex, vertex_name)?;
+    vertex_layout.resolve_runtime_arrays(arrays)?;
+    let fragment_interfaces = create_interfaces(fragment, fragment_name)?;
+    let mut fragment_layout = create_layouts(fragment, fragment_name)?;
+    fragment_layout.resolve_runtime_arrays(arrays)?;
     let frag_input = FragInput {
         inputs: fragment_interfaces.inputs,
     };
@@ -55,126 +137,618 @@ pub fn create_entry(shaders: &CompiledShaders) -> Result<Entry, Error> {
         frag_layout,
         vert_layout,
         compute_layout: Default::default(),
+        vert_info,
+        frag_info,
+        compute_info: Default::default(),
+        workgroup_size: None,
+        warnings,
     })
 }
 
-pub fn create_compute_entry(shaders: &CompiledShaders) -> Result<Entry, Error> {
-    create_layouts(&shaders.compute).map(|layout_data| {
-        let mut entry = Entry::default();
-        entry.compute_layout = ComputeLayout{ layout_data };
-        entry
+pub fn create_compute_entry(
+    shaders: &CompiledShaders,
+    arrays: &RuntimeArrays,
+) -> Result<Entry, Error> {
+    let m = backend::ShaderModule::load(&shaders.compute)?;
+    let mut layout_data = create_layouts(&m, None)?;
+    layout_data.resolve_runtime_arrays(arrays)?;
+    Ok(Entry {
+        compute_layout: ComputeLayout { layout_data },
+        compute_info: create_stage_info(m.module(), None),
+        workgroup_size: workgroup_size(m.module(), None),
+        ..Entry::default()
     })
 }
 
-fn create_interfaces(data: &[u32]) -> Result<ShaderInterfaces, Error> {
-    sr::ShaderModule::load_u32_data(data)
-        .map_err(|e| Error::LoadingData(e.to_string()))
-        .map(|m| {
-            let inputs = m
-                .enumerate_input_variables(None)
-                .map_err(|e| Error::LoadingData(e.to_string()))
-                .and_then(|inputs| {
-                    inputs
-                        .iter()
-                        .filter(|i| {
-                            !i.decoration_flags
-                                .contains(sr::types::ReflectDecorationFlags::BUILT_IN)
-                        })
-                        .map(|i| Ok(ShaderInterfaceDefEntry {
-                            location: i.location..(i.location + 1),
-                            format: SpirvTy::try_from(i.format)?.inner(),
-                            name: Some(Cow::from(i.name.clone())),
-                        }))
-                        .collect::<Result<Vec<ShaderInterfaceDefEntry>, _>>()
-                });
-            let outputs = m
-                .enumerate_output_variables(None)
-                .map_err(|e| Error::LoadingData(e.to_string()))
-                .and_then(|outputs| {
-                    outputs
-                        .iter()
-                        .filter(|i| {
-                            !i.decoration_flags
-                                .contains(sr::types::ReflectDecorationFlags::BUILT_IN)
-                        })
-                        .map(|i| Ok(ShaderInterfaceDefEntry {
-                            location: i.location..(i.location + 1),
-                            format: SpirvTy::try_from(i.format)?.inner(),
-                            name: Some(Cow::from(i.name.clone())),
-                        }))
-                        .collect::<Result<Vec<ShaderInterfaceDefEntry>, _>>()
-                });
-            inputs.and_then(|inputs| outputs.map(|outputs| ShaderInterfaces { inputs, outputs } ))
+// Capabilities and extensions are declared for the whole module
+// while built-ins depend on what the entry point's code touches.
+fn create_stage_info(module: &spirv::Module, entry_point: Option<&str>) -> StageInfo {
+    let (read, written) = module.built_ins(entry_point);
+    let built_ins = |ids: std::collections::BTreeSet<u32>| {
+        ids.into_iter().filter_map(spv::BuiltIn::from_u32).collect()
+    };
+    StageInfo {
+        capabilities: module.capabilities(),
+        extensions: module.extensions(),
+        built_ins_read: built_ins(read),
+        built_ins_written: built_ins(written),
+        execution_modes: execution_modes(module, entry_point),
+    }
+}
+
+fn execution_modes(module: &spirv::Module, entry_point: Option<&str>) -> ExecutionModes {
+    use spv::ExecutionMode as Mode;
+    let declared = |mode| module.execution_mode(entry_point, mode).is_some();
+    let depth = if declared(Mode::DepthGreater) {
+        Some(DepthMode::Greater)
+    } else if declared(Mode::DepthLess) {
+        Some(DepthMode::Less)
+    } else if declared(Mode::DepthUnchanged) {
+        Some(DepthMode::Unchanged)
+    } else {
+        None
+    };
+    ExecutionModes {
+        early_fragment_tests: declared(Mode::EarlyFragmentTests),
+        depth_replacing: declared(Mode::DepthReplacing),
+        depth,
+        origin_upper_left: declared(Mode::OriginUpperLeft),
+        pixel_center_integer: declared(Mode::PixelCenterInteger),
+        xfb: declared(Mode::Xfb),
+    }
+}
+
+// A `WorkgroupSize` built-in takes precedence over the execution mode.
+// glslang declares one when any dimension comes from a spec constant.
+fn workgroup_size(module: &spirv::Module, entry_point: Option<&str>) -> Option<WorkgroupSize> {
+    let dimension = |id| {
+        let constant = module.constant_declaration(id)?;
+        let value = constant.operands.get(2).copied()?;
+        Some((value, module.decoration(id, spv::Decoration::SpecId)))
+    };
+    let from_ids = |ids: &[u32]| {
+        let mut size = WorkgroupSize {
+            size: [1; 3],
+            spec_ids: [None; 3],
+        };
+        for (i, &id) in ids.iter().take(3).enumerate() {
+            let (value, spec_id) = dimension(id)?;
+            size.size[i] = value;
+            size.spec_ids[i] = spec_id;
+        }
+        Some(size)
+    };
+    let built_in = module.instructions.iter().find(|i| {
+        matches!(
+            i.op,
+            spv::Op::ConstantComposite | spv::Op::SpecConstantComposite
+        ) && module.decoration(i.operands[1], spv::Decoration::BuiltIn)
+            == Some(spv::BuiltIn::WorkgroupSize as u32)
+    });
+    if let Some(composite) = built_in {
+        return from_ids(&composite.operands[2..]);
+    }
+    if let Some(ids) = module.execution_mode(entry_point, spv::ExecutionMode::LocalSizeId) {
+        return from_ids(ids);
+    }
+    module
+        .execution_mode(entry_point, spv::ExecutionMode::LocalSize)
+        .filter(|size| size.len() >= 3)
+        .map(|size| WorkgroupSize {
+            size: [size[0], size[1], size[2]],
+            spec_ids: [None; 3],
         })
-    .and_then(|t| t)
-}
-
-fn create_layouts(data: &[u32]) -> Result<LayoutData, Error> {
-    sr::ShaderModule::load_u32_data(data)
-        .map(|m| {
-            let descs: Result<_, Error> = m
-                .enumerate_descriptor_sets(None)
-                .map_err(|e| Error::LoadingData(e.to_string()))
-                .and_then(|sets| {
-                    let num_sets = sets.len();
-                    let num_bindings = sets
-                        .iter()
-                        .map(|i| (i.set as usize, i.bindings.len()))
-                        .collect::<HashMap<usize, usize>>();
-                    let descriptions = sets
-                        .iter()
-                        .map(|i| {
-                            let desc = i
-                                .bindings
-                                .iter()
-                                .map(|b| {
-                                    let info = DescriptorDescInfo {
-                                        descriptor_type: b.descriptor_type,
-                                        image: b.image,
-                                    };
-                                    let ty = SpirvTy::<DescriptorDescTy>::try_from(info)?.inner();
-                                    let stages = ShaderStages::none();
-                                    let d = DescriptorDesc {
-                                        ty,
-                                        array_count: b.count,
-                                        stages,
-                                        // TODO this is what vulkan_shaders does but I don't think
-                                        // it's correct
-                                        readonly: true,
-                                    };
-                                    Ok((b.binding as usize, d))
-                                })
-                                .collect::<Result<HashMap<usize, DescriptorDesc>, Error>>();
-                            desc.and_then(|d| Ok((i.set as usize, d)))
-                        })
-                        .collect::<Result<HashMap<usize, _>, Error>>();
-                    descriptions.map(|d| (num_sets, num_bindings, d))
-                });
-            let pcs = m
-                .enumerate_push_constant_blocks(None)
-                .map_err(|e| Error::LoadingData(e.to_string()))
-                .map(|constants| {
-                    let num_constants = constants.len();
-                    let pc_ranges = constants
-                        .iter()
-                        .map(|pc| PipelineLayoutDescPcRange {
-                            offset: pc.offset as usize,
-                            size: pc.size as usize,
-                            stages: ShaderStages::all(),
-                        })
-                        .collect::<Vec<PipelineLayoutDescPcRange>>();
-                    (num_constants, pc_ranges)
-                });
-            descs.and_then(|(num_sets, num_bindings, descriptions)| {
-                pcs.map(|(num_constants, pc_ranges)| LayoutData {
-                    num_sets,
-                    num_bindings,
-                    descriptions,
-                    num_constants,
-                    pc_ranges,
-                })
+}
+
+pub fn create_entry_points(data: &[u32]) -> Result<Vec<EntryPoint>, Error> {
+    let m = backend::ShaderModule::load(data)?;
+    m.entry_points()?
+        .iter()
+        .map(|ep| {
+            let execution_model = known_model(ep.execution_model, &ep.name)?;
+            let interfaces = create_interfaces(&m, Some(&ep.name))?;
+            let layout_data = create_layouts(&m, Some(&ep.name))?;
+            Ok(EntryPoint {
+                name: ep.name.clone(),
+                execution_model,
+                inputs: interfaces.inputs,
+                outputs: interfaces.outputs,
+                layout_data,
+                info: create_stage_info(m.module(), Some(&ep.name)),
             })
         })
-        .map_err(|e| Error::LoadingData(e.to_string()))
-        .and_then(|t| t)
+        .collect()
+}
+
+fn find_entry_point(
+    m: &backend::ShaderModule,
+    name: &str,
+    model: ExecutionModel,
+) -> Result<(), Error> {
+    let found = m
+        .entry_points()?
+        .iter()
+        .any(|ep| ep.name == name && ep.execution_model == Some(model));
+    if found {
+        Ok(())
+    } else {
+        Err(Error::EntryPoint(format!(
+            "no {:?} entry point named {}",
+            model, name
+        )))
+    }
+}
+
+fn create_interfaces(
+    m: &backend::ShaderModule,
+    entry_point: Option<&str>,
+) -> Result<ShaderInterfaces, Error> {
+    let model = entry_point_model(m, entry_point)?;
+    let module = m.module();
+    // These stages see an array of every vertex in the primitive or patch
+    let per_vertex = |outputs: bool| match model {
+        ExecutionModel::TessellationControl => true,
+        ExecutionModel::TessellationEvaluation | ExecutionModel::Geometry => !outputs,
+        _ => false,
+    };
+    let entries = |vars: Vec<InterfaceVariable>, outputs| {
+        vars.iter()
+            .filter(|i| !i.built_in)
+            .map(|i| {
+                let arrayed = per_vertex(outputs)
+                    && !module.has_decoration(i.spirv_id, spv::Decoration::Patch);
+                let component = module
+                    .decoration(i.spirv_id, spv::Decoration::Component)
+                    .unwrap_or(0);
+                interface_entries(i, i.location, &i.name, arrayed)
+                    .map(|e| e.into_iter().map(move |e| (component, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|e| pack_components(e.into_iter().flatten()))
+    };
+    let inputs = entries(m.input_variables(entry_point)?, false)?;
+    let outputs = entries(m.output_variables(entry_point)?, true)?;
+    Ok(ShaderInterfaces { inputs, outputs })
+}
+
+struct Packed {
+    entry: ShaderInterfaceDefEntry,
+    names: Vec<(u32, String)>,
+    start: u32,
+    end: u32,
+}
+
+// Variables sharing a location through `component` qualifiers are
+// joined into one entry per location because vulkano matches interfaces
+// by location and only looks at the first entry it finds. The entry's
+// format spans the components the variables use, so a variable alone at
+// its locations keeps its own width, and its name lists the variables
+// in component order. Arrays and matrices that share only some of their
+// locations are split up so each location can be joined on its own.
+fn pack_components(
+    entries: impl Iterator<Item = (u32, ShaderInterfaceDefEntry)>,
+) -> Result<Vec<ShaderInterfaceDefEntry>, Error> {
+    let entries: Vec<_> = entries.collect();
+    let mut users: HashMap<u32, u32> = HashMap::new();
+    for location in entries.iter().flat_map(|(_, e)| e.location.clone()) {
+        *users.entry(location).or_insert(0) += 1;
+    }
+    let mut packed: Vec<Packed> = Vec::new();
+    for (component, entry) in entries {
+        let count = validation::component_count(entry.format).ok_or_else(|| {
+            Error::Layout(ConvertError::Unimplemented(format!("{:?}", entry.format)))
+        })?;
+        let end = component + count;
+        let name = entry.name.clone().unwrap_or_default().into_owned();
+        if entry.location.clone().all(|l| users[&l] == 1) {
+            packed.push(Packed {
+                entry,
+                names: vec![(component, name)],
+                start: component,
+                end,
+            });
+            continue;
+        }
+        for location in entry.location.clone() {
+            let name = (component, name.clone());
+            match packed
+                .iter_mut()
+                .find(|p| p.entry.location == (location..location + 1))
+            {
+                Some(p) => {
+                    p.start = component.min(p.start);
+                    p.end = end.max(p.end);
+                    p.entry.format = with_components(p.entry.format, p.end - p.start)?;
+                    p.names.push(name);
+                }
+                None => packed.push(Packed {
+                    entry: ShaderInterfaceDefEntry {
+                        location: location..(location + 1),
+                        format: entry.format,
+                        name: None,
+                    },
+                    names: vec![name],
+                    start: component,
+                    end,
+                }),
+            }
+        }
+    }
+    Ok(packed
+        .into_iter()
+        .map(|mut p| {
+            p.names.sort_by_key(|(c, _)| *c);
+            let names: Vec<_> = p.names.into_iter().map(|(_, n)| n).collect();
+            p.entry.name = Some(Cow::from(names.join(", ")));
+            p.entry
+        })
+        .collect())
+}
+
+// The same kind of format as `format` with a different number of components
+fn with_components(format: Format, components: u32) -> Result<Format, Error> {
+    let count = validation::component_count(format);
+    if count == Some(components) {
+        return Ok(format);
+    }
+    let width = format.size().unwrap_or(0) as u32 * 8 / count.unwrap_or(1).max(1);
+    let scalar = match format.ty() {
+        FormatTy::Uint => ScalarType::Uint(width),
+        FormatTy::Sint => ScalarType::Sint(width),
+        FormatTy::Float => ScalarType::Float(width),
+        _ => Err(Error::Layout(ConvertError::Unimplemented(format!(
+            "{:?} with {} components",
+            format, components
+        ))))?,
+    };
+    let ty = match components {
+        1 => MemberType::Scalar(scalar),
+        n => MemberType::Vector(scalar, n),
+    };
+    SpirvTy::try_from(ty).map(SpirvTy::inner)
+}
+
+/// Expands an interface variable into the locations it takes up.
+/// Matrices take a location per column and arrays one per element,
+/// while the members of a struct follow on from each other and get
+/// an entry each. With `per_vertex` the outer array is the vertices
+/// of a primitive and doesn't take up any locations.
+pub fn interface_entries(
+    var: &InterfaceVariable,
+    location: u32,
+    name: &str,
+    per_vertex: bool,
+) -> Result<Vec<ShaderInterfaceDefEntry>, Error> {
+    let dims = if per_vertex {
+        var.array.get(1..).unwrap_or(&[])
+    } else {
+        &var.array[..]
+    };
+    let elements: u32 = dims.iter().product();
+    if var.members.is_empty() {
+        let (scalar, components, columns) = match var.ty {
+            MemberType::Matrix { ty, columns, rows } => (Some(ty), rows, columns),
+            MemberType::Vector(ty, components) => (Some(ty), components, 1),
+            MemberType::Scalar(ty) => (Some(ty), 1, 1),
+            MemberType::Struct => (None, 1, 1),
+        };
+        // 64 bit vectors with more than two components need two locations
+        let wide = matches!(
+            scalar,
+            Some(ScalarType::Sint(64)) | Some(ScalarType::Uint(64)) | Some(ScalarType::Float(64))
+        );
+        let per_column = if wide && components > 2 { 2 } else { 1 };
+        return Ok(vec![ShaderInterfaceDefEntry {
+            location: location..(location + elements * columns * per_column),
+            format: SpirvTy::try_from(var.ty)?.inner(),
+            name: Some(Cow::from(name.to_string())),
+        }]);
+    }
+    let mut entries = Vec::new();
+    let mut next = location;
+    for element in 0..elements {
+        for member in &var.members {
+            let name = if dims.is_empty() {
+                format!("{}.{}", name, member.member_name)
+            } else {
+                format!("{}[{}].{}", name, element, member.member_name)
+            };
+            let member = interface_entries(member, next, &name, false)?;
+            next = member.last().map(|e| e.location.end).unwrap_or(next);
+            entries.extend(member);
+        }
+    }
+    Ok(entries)
+}
+
+fn create_layouts(
+    m: &backend::ShaderModule,
+    entry_point: Option<&str>,
+) -> Result<LayoutData, Error> {
+    let module = m.module();
+    let descs: Result<_, Error> = m.descriptor_sets(entry_point).and_then(|sets| {
+        let used = module.referenced_ids(entry_point);
+        let entry_stages = entry_point_stages(m, entry_point)?;
+        let descriptions = sets
+            .iter()
+            .map(|i| {
+                let desc = i
+                    .bindings
+                    .iter()
+                    .map(|b| {
+                        let info = DescriptorDescInfo {
+                            descriptor_type: b.descriptor_type,
+                            image: b.image,
+                        };
+                        let ty = SpirvTy::<DescriptorDescTy>::try_from(info)?.inner();
+                        let stages = if used.contains(&b.spirv_id) {
+                            entry_stages
+                        } else {
+                            ShaderStages::none()
+                        };
+                        let readonly = is_readonly(module, &ty, b.spirv_id);
+                        // Both backends count a runtime array as one
+                        let array_count = if module.is_runtime_array(b.spirv_id) {
+                            0
+                        } else {
+                            b.count
+                        };
+                        let d = DescriptorDesc {
+                            ty,
+                            array_count,
+                            stages,
+                            readonly,
+                        };
+                        Ok((b.binding as usize, d))
+                    })
+                    .collect::<Result<HashMap<usize, DescriptorDesc>, Error>>();
+                desc.and_then(|d| Ok((i.set as usize, d)))
+            })
+            .collect::<Result<HashMap<usize, _>, Error>>();
+        let binding_info = sets
+            .iter()
+            .map(|i| {
+                let info = i
+                    .bindings
+                    .iter()
+                    .map(|b| {
+                        let image = module.image_type(b.spirv_id);
+                        // The depth operand is 1 for depth comparison images
+                        let depth_comparison = image.map(|i| i.operands[3] == 1).unwrap_or(false);
+                        let name = match b.name.as_str() {
+                            "" => b.type_name.clone(),
+                            name => name.to_string(),
+                        };
+                        let info = BindingInfo {
+                            name,
+                            variable_count: module.is_runtime_array(b.spirv_id),
+                            depth_comparison,
+                            sampled_type: image.and_then(|i| sampled_type(module, i.operands[1])),
+                            members: b.members.clone(),
+                        };
+                        (b.binding as usize, info)
+                    })
+                    .collect();
+                (i.set as usize, info)
+            })
+            .collect();
+        descriptions.map(|d| (d, binding_info))
+    });
+    let pcs = m.push_constant_blocks(entry_point).and_then(|constants| {
+        let stages = entry_point_stages(m, entry_point)?;
+        let pc_ranges = constants
+            .iter()
+            .filter_map(|pc| used_pc_range(module, pc, entry_point, stages))
+            .collect::<Vec<PipelineLayoutDescPcRange>>();
+        let push_constants = constants
+            .iter()
+            .map(|pc| PushConstantBlock {
+                // spirv-reflect names push constants after their type
+                name: module.name(pc.spirv_id).unwrap_or_default(),
+                type_name: pc.type_name.clone(),
+                size: pc.size,
+                members: pc.members.clone(),
+            })
+            .collect();
+        Ok((pc_ranges.len(), pc_ranges, push_constants))
+    });
+    let spec_constants = spec_constants(module);
+    descs.and_then(|(descriptions, binding_info)| {
+        pcs.map(|(num_constants, pc_ranges, push_constants)| {
+            let mut data = LayoutData {
+                descriptions,
+                num_constants,
+                pc_ranges,
+                binding_info,
+                push_constants,
+                spec_constants,
+                ..LayoutData::default()
+            };
+            data.count_bindings();
+            data
+        })
+    })
+}
+
+// The execution model of an entry point. Without a name the
+// module is expected to hold a single stage.
+fn entry_point_model(
+    m: &backend::ShaderModule,
+    entry_point: Option<&str>,
+) -> Result<ExecutionModel, Error> {
+    match entry_point {
+        Some(name) => m
+            .entry_points()?
+            .into_iter()
+            .find(|ep| ep.name == name)
+            .ok_or_else(|| Error::EntryPoint(format!("no entry point named {}", name)))
+            .and_then(|ep| known_model(ep.execution_model, name)),
+        None => known_model(m.execution_model(), "module"),
+    }
+}
+
+// Stages vulkano doesn't have, like ray tracing, have no model
+fn known_model(model: Option<ExecutionModel>, name: &str) -> Result<ExecutionModel, Error> {
+    model.ok_or_else(|| Error::Layout(ConvertError::Unimplemented(format!("stage of {}", name))))
+}
+
+fn entry_point_stages(
+    m: &backend::ShaderModule,
+    entry_point: Option<&str>,
+) -> Result<ShaderStages, Error> {
+    entry_point_model(m, entry_point).map(ExecutionModel::stages)
+}
+
+fn sampled_type(module: &spirv::Module, id: u32) -> Option<SampledType> {
+    let ty = module.type_declaration(id)?;
+    match ty.op {
+        spv::Op::TypeFloat => Some(SampledType::Float),
+        spv::Op::TypeInt if ty.operands[2] == 1 => Some(SampledType::Sint),
+        spv::Op::TypeInt => Some(SampledType::Uint),
+        _ => None,
+    }
+}
+
+// Spec constants belong to the module rather than an entry point
+// and are listed by id.
+// Composite constants and operations on constants can't be given a
+// `constant_id` so only scalars are listed.
+fn spec_constants(module: &spirv::Module) -> Vec<SpecConstant> {
+    let mut constants = module
+        .instructions
+        .iter()
+        .filter(|i| {
+            matches!(
+                i.op,
+                spv::Op::SpecConstantTrue | spv::Op::SpecConstantFalse | spv::Op::SpecConstant
+            )
+        })
+        .filter_map(|i| {
+            let (ty, id) = (i.operands[0], i.operands[1]);
+            let spec_id = module.decoration(id, spv::Decoration::SpecId)?;
+            let ty = module.type_declaration(ty)?;
+            // Literals wider than 32 bits start with the low order word
+            let bits = i.operands[2..]
+                .iter()
+                .rev()
+                .fold(0u64, |v, &w| v << 32 | w as u64);
+            let (ty, default) = match ty.op {
+                spv::Op::TypeBool => (
+                    ScalarType::Bool,
+                    SpecValue::Bool(i.op == spv::Op::SpecConstantTrue),
+                ),
+                spv::Op::TypeInt => {
+                    let width = ty.operands[1];
+                    if ty.operands[2] == 1 {
+                        // Sign extend from the constant's width
+                        let shift = 64 - width;
+                        (
+                            ScalarType::Sint(width),
+                            SpecValue::Sint((bits << shift) as i64 >> shift),
+                        )
+                    } else {
+                        (ScalarType::Uint(width), SpecValue::Uint(bits))
+                    }
+                }
+                spv::Op::TypeFloat => {
+                    let width = ty.operands[1];
+                    let value = match width {
+                        16 => half_to_f64(bits as u16),
+                        64 => f64::from_bits(bits),
+                        _ => f32::from_bits(bits as u32) as f64,
+                    };
+                    (ScalarType::Float(width), SpecValue::Float(value))
+                }
+                _ => return None,
+            };
+            Some(SpecConstant {
+                id: spec_id,
+                name: module.name(id).unwrap_or_default(),
+                ty,
+                default,
+            })
+        })
+        .collect::<Vec<_>>();
+    constants.sort_by_key(|c| c.id);
+    constants
+}
+
+fn half_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f64;
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        0x1f if mantissa == 0.0 => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+    }
+}
+
+// Only storage resources can be written by a shader and
+// those are readonly when declared with the `readonly` qualifier.
+fn is_readonly(module: &spirv::Module, ty: &DescriptorDescTy, var: u32) -> bool {
+    match ty {
+        DescriptorDescTy::Buffer(DescriptorBufferDesc { storage: true, .. })
+        | DescriptorDescTy::TexelBuffer { storage: true, .. }
+        | DescriptorDescTy::Image(DescriptorImageDesc { sampled: false, .. }) => {
+            module.is_non_writable(var)
+        }
+        _ => true,
+    }
+}
+
+// The smallest range covering the members of the block that the
+// entry point uses, widened to the 4 byte alignment Vulkan needs.
+fn used_pc_range(
+    module: &spirv::Module,
+    pc: &backend::BlockVariable,
+    entry_point: Option<&str>,
+    stages: ShaderStages,
+) -> Option<PipelineLayoutDescPcRange> {
+    let used = module.used_members(pc.spirv_id, pc.members.len(), entry_point);
+    let members = || used.iter().filter_map(|&i| pc.members.get(i));
+    let start = members().map(|m| m.offset).min()? & !3;
+    let end = (members().map(|m| m.offset + m.size).max()? + 3) & !3;
+    Some(PipelineLayoutDescPcRange {
+        offset: start as usize,
+        size: (end - start) as usize,
+        stages,
+    })
+}
+
+fn check_push_constants(
+    (vertex, vertex_name): (&backend::ShaderModule, Option<&str>),
+    (fragment, fragment_name): (&backend::ShaderModule, Option<&str>),
+) -> Result<(), Error> {
+    let vertex = vertex.push_constant_blocks(vertex_name)?;
+    let fragment = fragment.push_constant_blocks(fragment_name)?;
+    for a in vertex.iter().flat_map(|b| &b.members) {
+        for b in fragment.iter().flat_map(|b| &b.members) {
+            let overlaps = a.offset < b.offset + b.size && b.offset < a.offset + a.size;
+            if overlaps && !same_block_layout(a, b) {
+                return Err(Error::Merge(MergeError::PushConstant {
+                    offset: a.offset.max(b.offset) as usize,
+                }));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Names are allowed to differ between stages but
+// everything that affects the memory layout has to match.
+fn same_block_layout(a: &BlockMember, b: &BlockMember) -> bool {
+    a.offset == b.offset
+        && a.size == b.size
+        && a.ty == b.ty
+        && a.array == b.array
+        && a.array_stride == b.array_stride
+        && a.matrix_stride == b.matrix_stride
+        && a.row_major == b.row_major
+        && a.members.len() == b.members.len()
+        && a.members
+            .iter()
+            .zip(&b.members)
+            .all(|(a, b)| same_block_layout(a, b))
 }
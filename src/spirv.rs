@@ -0,0 +1,538 @@
+// A small reader for the raw SPIR-V words. This fills in the
+// information that spirv-reflect doesn't give us, like which
+// members of a block an entry point actually touches.
+use crate::error::{Error, ReflectError};
+use num_traits::FromPrimitive;
+use spirv_headers as spv;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+
+pub struct Instruction {
+    pub op: spv::Op,
+    pub operands: Vec<u32>,
+}
+
+pub struct Module {
+    pub instructions: Vec<Instruction>,
+    functions: HashMap<u32, Range<usize>>,
+    // Indices into `instructions` so lookups by id don't scan the module
+    types: HashMap<u32, usize>,
+    values: HashMap<u32, usize>,
+    names: HashMap<u32, usize>,
+    member_names: HashMap<(u32, u32), usize>,
+    decorations: HashMap<u32, Vec<usize>>,
+    member_decorations: HashMap<u32, Vec<usize>>,
+}
+
+impl Module {
+    pub fn new(data: &[u32]) -> Result<Self, Error> {
+        if data.len() < 5 || data[0] != spv::MAGIC_NUMBER {
+            return Err(Error::Reflect(ReflectError::NotSpirv));
+        }
+        let mut module = Module {
+            instructions: Vec::new(),
+            functions: HashMap::new(),
+            types: HashMap::new(),
+            values: HashMap::new(),
+            names: HashMap::new(),
+            member_names: HashMap::new(),
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+        };
+        let mut current = None;
+        let mut words = &data[5..];
+        while !words.is_empty() {
+            let word = data.len() - words.len();
+            let count = (words[0] >> 16) as usize;
+            if count == 0 || count > words.len() {
+                return Err(Error::Reflect(ReflectError::Instruction { word }));
+            }
+            // Opcodes newer than our headers are kept as no-ops
+            let op = spv::Op::from_u32(words[0] & 0xffff).unwrap_or(spv::Op::Nop);
+            let operands = words[1..count].to_vec();
+            if operands.len() < min_operands(op) {
+                return Err(Error::Reflect(ReflectError::Instruction { word }));
+            }
+            let index = module.instructions.len();
+            match op {
+                spv::Op::Function => current = Some((operands[1], index)),
+                spv::Op::FunctionEnd => {
+                    if let Some((id, start)) = current.take() {
+                        module.functions.insert(id, start..index + 1);
+                    }
+                }
+                spv::Op::Name => {
+                    module.names.entry(operands[0]).or_insert(index);
+                }
+                spv::Op::MemberName => {
+                    module
+                        .member_names
+                        .entry((operands[0], operands[1]))
+                        .or_insert(index);
+                }
+                spv::Op::Decorate => module
+                    .decorations
+                    .entry(operands[0])
+                    .or_default()
+                    .push(index),
+                spv::Op::MemberDecorate => module
+                    .member_decorations
+                    .entry(operands[0])
+                    .or_default()
+                    .push(index),
+                spv::Op::Variable => {
+                    module.values.insert(operands[1], index);
+                }
+                op if declares_constant(op) => {
+                    module.values.insert(operands[1], index);
+                }
+                op if declares_type(op) && !operands.is_empty() => {
+                    module.types.entry(operands[0]).or_insert(index);
+                }
+                _ => (),
+            }
+            module.instructions.push(Instruction { op, operands });
+            words = &words[count..];
+        }
+        Ok(module)
+    }
+
+    fn function(&self, id: u32) -> &[Instruction] {
+        self.functions
+            .get(&id)
+            .map(|r| &self.instructions[r.clone()])
+            .unwrap_or(&[])
+    }
+
+    fn entry_point_function(&self, name: &str) -> Option<u32> {
+        self.instructions
+            .iter()
+            .filter(|i| i.op == spv::Op::EntryPoint)
+            .find(|i| string(&i.operands[2..]).0 == name)
+            .map(|i| i.operands[1])
+    }
+
+    // With no name the module is expected to hold a single entry point
+    fn entry_point_or_only(&self, entry_point: Option<&str>) -> Option<u32> {
+        match entry_point {
+            Some(name) => self.entry_point_function(name),
+            None => self
+                .instructions
+                .iter()
+                .find(|i| i.op == spv::Op::EntryPoint)
+                .map(|i| i.operands[1]),
+        }
+    }
+
+    /// The operands of an execution mode declared for the entry point.
+    /// Modes that take ids, like `LocalSizeId`, give the ids.
+    pub fn execution_mode(
+        &self,
+        entry_point: Option<&str>,
+        mode: spv::ExecutionMode,
+    ) -> Option<&[u32]> {
+        let function = self.entry_point_or_only(entry_point)?;
+        self.instructions
+            .iter()
+            .find(|i| {
+                (i.op == spv::Op::ExecutionMode || i.op == spv::Op::ExecutionModeId)
+                    && i.operands[0] == function
+                    && i.operands[1] == mode as u32
+            })
+            .map(|i| &i.operands[2..])
+    }
+
+    /// All the functions that can be called from this entry point.
+    /// With no entry point every function in the module is returned.
+    pub fn reachable_functions(&self, entry_point: Option<&str>) -> HashSet<u32> {
+        let start = match entry_point {
+            Some(name) => match self.entry_point_function(name) {
+                Some(id) => id,
+                None => return HashSet::new(),
+            },
+            None => return self.functions.keys().copied().collect(),
+        };
+        let mut reachable = HashSet::new();
+        let mut stack = vec![start];
+        while let Some(id) = stack.pop() {
+            if reachable.insert(id) {
+                stack.extend(
+                    self.function(id)
+                        .iter()
+                        .filter(|i| i.op == spv::Op::FunctionCall)
+                        .map(|i| i.operands[2]),
+                );
+            }
+        }
+        reachable
+    }
+
+    fn reachable_instructions<'a>(
+        &'a self,
+        entry_point: Option<&str>,
+    ) -> impl Iterator<Item = &'a Instruction> {
+        self.reachable_functions(entry_point)
+            .into_iter()
+            .flat_map(move |f| self.function(f).iter())
+    }
+
+    /// Every id that the entry point's code refers to.
+    /// A global variable in here is statically used.
+    pub fn referenced_ids(&self, entry_point: Option<&str>) -> HashSet<u32> {
+        self.reachable_instructions(entry_point)
+            .flat_map(|i| i.operands.iter().copied())
+            .collect()
+    }
+
+    pub fn constant(&self, id: u32) -> Option<u32> {
+        self.constant_declaration(id)
+            .filter(|i| i.op == spv::Op::Constant)
+            .map(|i| i.operands[2])
+    }
+
+    /// The capabilities the module declares.
+    pub fn capabilities(&self) -> Vec<spv::Capability> {
+        self.instructions
+            .iter()
+            .filter(|i| i.op == spv::Op::Capability)
+            .filter_map(|i| spv::Capability::from_u32(i.operands[0]))
+            .collect()
+    }
+
+    /// The SPIR-V extensions the module declares, like `SPV_KHR_multiview`.
+    pub fn extensions(&self) -> Vec<String> {
+        self.instructions
+            .iter()
+            .filter(|i| i.op == spv::Op::Extension)
+            .map(|i| string(&i.operands).0)
+            .collect()
+    }
+
+    /// The instruction that declares a constant or spec constant.
+    pub fn constant_declaration(&self, id: u32) -> Option<&Instruction> {
+        self.value(id).filter(|i| declares_constant(i.op))
+    }
+
+    /// The `OpVariable` that declares a variable.
+    pub fn variable(&self, id: u32) -> Option<&Instruction> {
+        self.value(id).filter(|i| i.op == spv::Op::Variable)
+    }
+
+    fn value(&self, id: u32) -> Option<&Instruction> {
+        self.values.get(&id).map(|&i| &self.instructions[i])
+    }
+
+    /// The indices of the members of a block variable that the entry point uses.
+    /// Using the whole variable at once counts as using every member.
+    pub fn used_members(
+        &self,
+        var: u32,
+        member_count: usize,
+        entry_point: Option<&str>,
+    ) -> BTreeSet<usize> {
+        let mut used = BTreeSet::new();
+        for i in self.reachable_instructions(entry_point) {
+            let member = match i.op {
+                spv::Op::AccessChain | spv::Op::InBoundsAccessChain if i.operands[2] == var => {
+                    i.operands.get(3).and_then(|&index| self.constant(index))
+                }
+                _ if i.operands.contains(&var) => None,
+                _ => continue,
+            };
+            match member {
+                Some(m) => {
+                    used.insert(m as usize);
+                }
+                None => return (0..member_count).collect(),
+            }
+        }
+        used
+    }
+
+    /// The debug name given to an id with `OpName`.
+    pub fn name(&self, id: u32) -> Option<String> {
+        self.names
+            .get(&id)
+            .map(|&i| string(&self.instructions[i].operands[1..]).0)
+    }
+
+    /// The debug name given to a member of a struct with `OpMemberName`.
+    // Only the pure Rust reader needs these
+    #[cfg(any(test, feature = "native-reflect", not(feature = "spirv-reflect")))]
+    pub fn member_name(&self, id: u32, member: u32) -> Option<String> {
+        self.member_names
+            .get(&(id, member))
+            .map(|&i| string(&self.instructions[i].operands[2..]).0)
+    }
+
+    fn decorations(
+        &self,
+        id: u32,
+        decoration: spv::Decoration,
+    ) -> impl Iterator<Item = &Instruction> {
+        self.decorations
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(move |&i| &self.instructions[i])
+            .filter(move |i| i.operands[1] == decoration as u32)
+    }
+
+    pub fn has_decoration(&self, id: u32, decoration: spv::Decoration) -> bool {
+        self.decorations(id, decoration).next().is_some()
+    }
+
+    /// The first literal of a decoration, like the number of a `Component`.
+    pub fn decoration(&self, id: u32, decoration: spv::Decoration) -> Option<u32> {
+        self.decorations(id, decoration)
+            .next()
+            .and_then(|i| i.operands.get(2).copied())
+    }
+
+    // The `OpMemberDecorate`s of a struct type with the decoration
+    fn member_decorations(
+        &self,
+        id: u32,
+        decoration: spv::Decoration,
+    ) -> impl Iterator<Item = &Instruction> {
+        self.member_decorations
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(move |&i| &self.instructions[i])
+            .filter(move |i| i.operands[2] == decoration as u32)
+    }
+
+    pub fn has_member_decoration(&self, id: u32, member: u32, decoration: spv::Decoration) -> bool {
+        self.member_decorations(id, decoration)
+            .any(|i| i.operands[1] == member)
+    }
+
+    /// The first literal of a decoration on a member of a struct type.
+    // Only the pure Rust reader needs these
+    #[cfg(any(test, feature = "native-reflect", not(feature = "spirv-reflect")))]
+    pub fn member_decoration(
+        &self,
+        id: u32,
+        member: u32,
+        decoration: spv::Decoration,
+    ) -> Option<u32> {
+        self.member_decorations(id, decoration)
+            .find(|i| i.operands[1] == member)
+            .and_then(|i| i.operands.get(3).copied())
+    }
+
+    pub fn type_declaration(&self, id: u32) -> Option<&Instruction> {
+        self.types.get(&id).map(|&i| &self.instructions[i])
+    }
+
+    // The type a variable holds with the pointer
+    // and any arrays around it stripped off.
+    fn base_type(&self, var: u32) -> Option<u32> {
+        let mut id = self.variable(var)?.operands[0];
+        loop {
+            let ty = self.type_declaration(id)?;
+            match ty.op {
+                spv::Op::TypePointer => id = ty.operands[2],
+                spv::Op::TypeArray | spv::Op::TypeRuntimeArray => id = ty.operands[1],
+                _ => return Some(id),
+            }
+        }
+    }
+
+    /// The `OpTypeImage` of an image or combined image sampler variable.
+    pub fn image_type(&self, var: u32) -> Option<&Instruction> {
+        let ty = self
+            .base_type(var)
+            .and_then(|id| self.type_declaration(id))?;
+        let ty = match ty.op {
+            spv::Op::TypeSampledImage => self.type_declaration(ty.operands[1])?,
+            _ => ty,
+        };
+        Some(ty).filter(|ty| ty.op == spv::Op::TypeImage)
+    }
+
+    /// Whether a variable is an array of descriptors with no size,
+    /// like `uniform texture2D textures[]`.
+    pub fn is_runtime_array(&self, var: u32) -> bool {
+        self.variable(var)
+            .and_then(|v| self.type_declaration(v.operands[0]))
+            .filter(|ptr| ptr.op == spv::Op::TypePointer)
+            .and_then(|ptr| self.type_declaration(ptr.operands[2]))
+            .map(|ty| ty.op == spv::Op::TypeRuntimeArray)
+            .unwrap_or(false)
+    }
+
+    /// Whether a resource can't be written through, either because the
+    /// variable is `NonWritable` or every member of its block is.
+    pub fn is_non_writable(&self, var: u32) -> bool {
+        if self.has_decoration(var, spv::Decoration::NonWritable) {
+            return true;
+        }
+        self.base_type(var)
+            .and_then(|id| self.type_declaration(id))
+            .filter(|ty| ty.op == spv::Op::TypeStruct && ty.operands.len() > 1)
+            .map(|ty| {
+                (0..ty.operands.len() as u32 - 1).all(|m| {
+                    self.has_member_decoration(ty.operands[0], m, spv::Decoration::NonWritable)
+                })
+            })
+            .unwrap_or(false)
+    }
+
+    /// The built-ins the entry point reads and the ones it writes. These are
+    /// either variables decorated `BuiltIn` or members of a block like
+    /// `gl_PerVertex`, which are told apart by the member index used.
+    pub fn built_ins(&self, entry_point: Option<&str>) -> (BTreeSet<u32>, BTreeSet<u32>) {
+        let interface: HashSet<u32> = self
+            .instructions
+            .iter()
+            .filter(|i| {
+                i.op == spv::Op::Variable
+                    && (i.operands[2] == spv::StorageClass::Input as u32
+                        || i.operands[2] == spv::StorageClass::Output as u32)
+            })
+            .map(|i| i.operands[1])
+            .collect();
+        let mut chains: HashMap<u32, (u32, &[u32])> = HashMap::new();
+        let mut read = BTreeSet::new();
+        let mut written = BTreeSet::new();
+        for i in self.reachable_instructions(entry_point) {
+            let (pointer, store) = match i.op {
+                spv::Op::AccessChain | spv::Op::InBoundsAccessChain => {
+                    chains.insert(i.operands[1], (i.operands[2], &i.operands[3..]));
+                    continue;
+                }
+                spv::Op::Load => (i.operands[2], false),
+                spv::Op::Store => (i.operands[0], true),
+                _ => continue,
+            };
+            // Walk back to the variable, keeping the indices of the first chain
+            let (mut var, mut indices): (u32, &[u32]) = (pointer, &[]);
+            while let Some(&(base, chain_indices)) = chains.get(&var) {
+                var = base;
+                indices = chain_indices;
+            }
+            if !interface.contains(&var) {
+                continue;
+            }
+            let used = if store { &mut written } else { &mut read };
+            used.extend(self.variable_built_ins(var, indices));
+        }
+        (read, written)
+    }
+
+    // The built-ins reached through `indices` into a variable
+    fn variable_built_ins(&self, var: u32, indices: &[u32]) -> Vec<u32> {
+        if let Some(b) = self.decoration(var, spv::Decoration::BuiltIn) {
+            return vec![b];
+        }
+        let block = match self.base_type(var) {
+            Some(block) => block,
+            None => return Vec::new(),
+        };
+        let members: Vec<(u32, u32)> = self
+            .member_decorations(block, spv::Decoration::BuiltIn)
+            .filter_map(|i| Some((i.operands[1], *i.operands.get(3)?)))
+            .collect();
+        // Per vertex blocks like `gl_in[]` are indexed by vertex first
+        let arrayed = self
+            .variable(var)
+            .and_then(|v| self.type_declaration(v.operands[0]))
+            .and_then(|ptr| self.type_declaration(ptr.operands[2]))
+            .map(|ty| ty.op == spv::Op::TypeArray || ty.op == spv::Op::TypeRuntimeArray)
+            .unwrap_or(false);
+        let member = indices
+            .get(if arrayed { 1 } else { 0 })
+            .and_then(|&index| self.constant(index));
+        members
+            .into_iter()
+            .filter(|&(m, _)| member.map(|member| member == m).unwrap_or(true))
+            .map(|(_, b)| b)
+            .collect()
+    }
+
+    /// The variables that the entry point stores to, either
+    /// directly or through a pointer into the variable.
+    pub fn written_variables(&self, entry_point: Option<&str>) -> HashSet<u32> {
+        let mut bases = HashMap::new();
+        let mut written = HashSet::new();
+        let base = |bases: &HashMap<u32, u32>, mut id| {
+            while let Some(&b) = bases.get(&id) {
+                id = b;
+            }
+            id
+        };
+        for i in self.reachable_instructions(entry_point) {
+            match i.op {
+                spv::Op::AccessChain
+                | spv::Op::InBoundsAccessChain
+                | spv::Op::PtrAccessChain
+                | spv::Op::InBoundsPtrAccessChain => {
+                    bases.insert(i.operands[1], i.operands[2]);
+                }
+                spv::Op::Store | spv::Op::CopyMemory | spv::Op::CopyMemorySized => {
+                    written.insert(base(&bases, i.operands[0]));
+                }
+                // A pointer handed to another function might be written through
+                spv::Op::FunctionCall => {
+                    written.extend(i.operands[3..].iter().map(|&a| base(&bases, a)));
+                }
+                _ => (),
+            }
+        }
+        written
+    }
+}
+
+// The fewest operands an instruction can have for the lookups
+// here to index them directly. Checked once when loading.
+fn min_operands(op: spv::Op) -> usize {
+    use spv::Op::*;
+    match op {
+        Capability => 1,
+        Name | Decorate | ExecutionMode | ExecutionModeId | Store | CopyMemory | TypeFloat
+        | TypeSampledImage | TypeRuntimeArray => 2,
+        MemberName
+        | MemberDecorate
+        | EntryPoint
+        | FunctionCall
+        | Load
+        | CopyMemorySized
+        | Variable
+        | Constant
+        | TypeInt
+        | TypeVector
+        | TypeMatrix
+        | TypeArray
+        | TypePointer
+        | AccessChain
+        | InBoundsAccessChain
+        | PtrAccessChain
+        | InBoundsPtrAccessChain => 3,
+        Function => 4,
+        TypeImage => 8,
+        op if declares_constant(op) => 2,
+        _ => 0,
+    }
+}
+
+fn declares_type(op: spv::Op) -> bool {
+    (spv::Op::TypeVoid as u32..spv::Op::TypeForwardPointer as u32).contains(&(op as u32))
+}
+
+pub fn declares_constant(op: spv::Op) -> bool {
+    (spv::Op::ConstantTrue as u32..=spv::Op::SpecConstantOp as u32).contains(&(op as u32))
+}
+
+/// Decodes a null terminated literal string and returns it
+/// along with the number of words it took up.
+pub fn string(words: &[u32]) -> (String, usize) {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|w| w.to_le_bytes().to_vec())
+        .take_while(|&b| b != 0)
+        .collect();
+    let len = bytes.len() / 4 + 1;
+    (String::from_utf8_lossy(&bytes).into_owned(), len)
+}
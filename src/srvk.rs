@@ -1,6 +1,8 @@
+use crate::backend::{DescriptorType, ImageTraits};
 use crate::error::{ConvertError, Error};
-use crate::sr;
+use crate::layouts::{MemberType, ScalarType};
 use crate::vk;
+use spirv_headers as spv;
 use std::convert::TryFrom;
 use vk::descriptor::descriptor::*;
 use vk::format::Format;
@@ -10,8 +12,8 @@ pub struct SpirvTy<T> {
 }
 
 pub struct DescriptorDescInfo {
-    pub descriptor_type: sr::types::ReflectDescriptorType,
-    pub image: sr::types::ReflectImageTraits,
+    pub descriptor_type: DescriptorType,
+    pub image: Option<ImageTraits>,
 }
 
 impl<T> SpirvTy<T> {
@@ -23,170 +25,212 @@ impl<T> SpirvTy<T> {
 impl TryFrom<DescriptorDescInfo> for SpirvTy<DescriptorDescTy> {
     type Error = Error;
     fn try_from(d: DescriptorDescInfo) -> Result<Self, Self::Error> {
-        use sr::types::ReflectDescriptorType as SR;
         use DescriptorDescTy as VK;
-        match d.descriptor_type {
-            SR::Undefined => Err(ConvertError::Unimplemented(format!("{:?}", d.descriptor_type))),
-            SR::Sampler => Ok(VK::Sampler),
-            SR::CombinedImageSampler => Ok(VK::CombinedImageSampler(
-                SpirvTy::try_from(d.image)?.inner(),
-            )),
-            SR::SampledImage => Ok(VK::Image(SpirvTy::try_from(d.image)?.inner())),
-            SR::StorageImage => Err(ConvertError::Unimplemented(format!("{:?}", d.descriptor_type))),
-            SR::UniformTexelBuffer => Ok(VK::TexelBuffer {
+        use DescriptorType as D;
+        let image = || {
+            d.image.ok_or_else(|| {
+                Error::Layout(ConvertError::Unimplemented(format!(
+                    "{:?} without an image",
+                    d.descriptor_type
+                )))
+            })
+        };
+        let t = match d.descriptor_type {
+            D::Sampler => VK::Sampler,
+            D::CombinedImageSampler => {
+                VK::CombinedImageSampler(SpirvTy::try_from(image()?)?.inner())
+            }
+            D::SampledImage => VK::Image(SpirvTy::try_from(image()?)?.inner()),
+            D::StorageImage => VK::Image(SpirvTy::try_from(image()?)?.inner()),
+            D::UniformTexelBuffer => VK::TexelBuffer {
                 storage: false,
                 format: None,
-            }),
-            SR::StorageTexelBuffer => Ok(VK::TexelBuffer {
+            },
+            D::StorageTexelBuffer => VK::TexelBuffer {
+                storage: true,
+                format: SpirvTy::from(image()?.format).inner(),
+            },
+            D::UniformBuffer => VK::Buffer(DescriptorBufferDesc {
+                dynamic: Some(false),
                 storage: false,
-                format: None,
             }),
-            SR::UniformBuffer => Ok(VK::Buffer(DescriptorBufferDesc{ dynamic: Some(false), storage: false })),
-            SR::StorageBuffer => Ok(VK::Buffer(DescriptorBufferDesc{ dynamic: Some(false), storage: true })),
-            SR::UniformBufferDynamic => Ok(VK::Buffer(DescriptorBufferDesc{ dynamic: Some(true), storage: false })),
-            SR::StorageBufferDynamic => Ok(VK::Buffer(DescriptorBufferDesc{ dynamic: Some(true), storage: true })),
-            SR::InputAttachment => Ok(SpirvTy::try_from(d.image)?.inner()),
-            SR::AccelerationStructureNV=> Err(ConvertError::Unimplemented(format!("{:?}", d.descriptor_type))),
-        }
-        .map(|t| SpirvTy { inner: t })
-        .map_err(Error::Layout)
+            D::StorageBuffer => VK::Buffer(DescriptorBufferDesc {
+                dynamic: Some(false),
+                storage: true,
+            }),
+            D::InputAttachment => SpirvTy::try_from(image()?)?.inner(),
+        };
+        Ok(SpirvTy { inner: t })
     }
 }
 
-impl TryFrom<sr::types::ReflectImageTraits> for SpirvTy<DescriptorImageDesc> {
+// The shader can't know how many layers the image view will
+// have so arrayed images don't give a maximum
+fn array_layers(arrayed: bool) -> DescriptorImageDescArray {
+    if arrayed {
+        DescriptorImageDescArray::Arrayed { max_layers: None }
+    } else {
+        DescriptorImageDescArray::NonArrayed
+    }
+}
+
+impl TryFrom<ImageTraits> for SpirvTy<DescriptorImageDesc> {
     type Error = Error;
-    fn try_from(d: sr::types::ReflectImageTraits) -> Result<Self, Self::Error> {
-        let conv_array_layers = |a, d| {
-            if a != 0 {
-                DescriptorImageDescArray::Arrayed {
-                    max_layers: Some(d),
-                }
-            } else {
-                DescriptorImageDescArray::NonArrayed
-            }
-        };
+    fn try_from(d: ImageTraits) -> Result<Self, Self::Error> {
         let t = DescriptorImageDesc {
-            sampled: d.sampled != 0,
+            sampled: d.sampled,
             dimensions: SpirvTy::try_from(d.dim)?.inner(),
-            // TODO figure out how to do format correctly
-            //format: Some(SpirvTy::from(d.image_format).inner()),
-            format: None,
-            multisampled: d.ms != 0,
-            array_layers: conv_array_layers(d.arrayed, d.depth),
+            format: SpirvTy::from(d.format).inner(),
+            multisampled: d.multisampled,
+            array_layers: array_layers(d.arrayed),
         };
         Ok(SpirvTy { inner: t })
     }
 }
 
-impl TryFrom<sr::types::ReflectImageTraits> for SpirvTy<DescriptorDescTy> {
+impl TryFrom<ImageTraits> for SpirvTy<DescriptorDescTy> {
     type Error = Error;
-    fn try_from(d: sr::types::ReflectImageTraits) -> Result<Self, Self::Error> {
-        let conv_array_layers = |a, d| {
-            if a != 0 {
-                DescriptorImageDescArray::Arrayed {
-                    max_layers: Some(d),
-                }
-            } else {
-                DescriptorImageDescArray::NonArrayed
-            }
-        };
+    fn try_from(d: ImageTraits) -> Result<Self, Self::Error> {
         let t = DescriptorDescTy::InputAttachment {
-            multisampled: d.ms != 0,
-            array_layers: conv_array_layers(d.arrayed, d.depth),
+            multisampled: d.multisampled,
+            array_layers: array_layers(d.arrayed),
         };
         Ok(SpirvTy { inner: t })
     }
 }
-impl TryFrom<sr::types::variable::ReflectDimension> for SpirvTy<DescriptorImageDescDimensions> {
+impl TryFrom<spv::Dim> for SpirvTy<DescriptorImageDescDimensions> {
     type Error = Error;
-    fn try_from(d: sr::types::variable::ReflectDimension) -> Result<Self, Self::Error> {
-        use sr::types::variable::ReflectDimension::*;
+    fn try_from(d: spv::Dim) -> Result<Self, Self::Error> {
+        use spv::Dim::*;
         use DescriptorImageDescDimensions::*;
         match d {
-            Type1d => Ok(OneDimensional),
-            Type2d => Ok(TwoDimensional),
-            Type3d => Ok(ThreeDimensional),
-            sr::types::variable::ReflectDimension::Cube => Ok(DescriptorImageDescDimensions::Cube),
-            _ => Err(ConvertError::Unimplemented(format!("{:?}", d))),
+            Dim1D => Ok(OneDimensional),
+            Dim2D => Ok(TwoDimensional),
+            Dim3D => Ok(ThreeDimensional),
+            DimCube => Ok(Cube),
+            // Rectangle textures are plain 2D images in Vulkan
+            DimRect => Ok(TwoDimensional),
+            // These belong to texel buffers and input attachments
+            // which don't have image dimensions
+            DimBuffer | DimSubpassData => Err(ConvertError::Dimension(format!("{:?}", d))),
         }
         .map(|t| SpirvTy { inner: t })
         .map_err(Error::Layout)
     }
 }
 
-// I think this is wrong and currently is unused
-impl From<sr::types::image::ReflectImageFormat> for SpirvTy<Format> {
-    fn from(d: sr::types::image::ReflectImageFormat) -> Self {
-        use sr::types::image::ReflectImageFormat::*;
+// Unknown is what sampled images and images without
+// a format qualifier have so there is no format to give
+impl From<spv::ImageFormat> for SpirvTy<Option<Format>> {
+    fn from(d: spv::ImageFormat) -> Self {
+        use spv::ImageFormat as F;
         use Format::*;
         let inner = match d {
-            Undefined => unimplemented!(),
-            RGBA32_FLOAT => R32G32B32A32Sfloat,
-            RGBA16_FLOAT => R16G16B16A16Sfloat,
-            R32_FLOAT => R32Sfloat,
-            RGBA8 => unimplemented!(),
-            RGBA8_SNORM => R8G8B8A8Snorm,
-            RG32_FLOAT => R32G32Sfloat,
-            RG16_FLOAT => R16G16Sfloat,
-            R11G11B10_FLOAT => unimplemented!(),
-            R16_FLOAT => R16Sfloat,
-            RGBA16 => unimplemented!(),
-            RGB10A2 => unimplemented!(),
-            RG16 => unimplemented!(),
-            RG8 => unimplemented!(),
-            R16 => unimplemented!(),
-            R8 => unimplemented!(),
-            RGBA16_SNORM => R16G16B16A16Snorm,
-            RG16_SNORM => R16G16Snorm,
-            RG8_SNORM => R8G8Snorm,
-            R16_SNORM => R16Snorm,
-            R8_SNORM => R8Snorm,
-            RGBA32_INT => R32G32B32A32Sint,
-            RGBA16_INT => R16G16B16A16Sint,
-            RGBA8_INT => R8G8B8A8Sint,
-            R32_INT => R32Sint,
-            RG32_INT => R32G32Sint,
-            RG16_INT => R16G16Sint,
-            RG8_INT => R8G8Sint,
-            R16_INT => R16Sint,
-            R8_INT => R8Sint,
-            RGBA32_UINT => R32G32B32A32Uint,
-            RGBA16_UINT => R16G16B16A16Uint,
-            RGBA8_UINT => R8G8B8A8Uint,
-            R32_UINT => R32Uint,
-            RGB10A2_UINT => A2R10G10B10UintPack32,
-            RG32_UINT => R32G32Uint,
-            RG16_UINT => R16G16Uint,
-            RG8_UINT => R8G8Uint,
-            R16_UINT => R16Uint,
-            R8_UINT => R8Uint,
+            F::Unknown => None,
+            F::Rgba32f => Some(R32G32B32A32Sfloat),
+            F::Rgba16f => Some(R16G16B16A16Sfloat),
+            F::R32f => Some(R32Sfloat),
+            F::Rgba8 => Some(R8G8B8A8Unorm),
+            F::Rgba8Snorm => Some(R8G8B8A8Snorm),
+            F::Rg32f => Some(R32G32Sfloat),
+            F::Rg16f => Some(R16G16Sfloat),
+            F::R11fG11fB10f => Some(B10G11R11UfloatPack32),
+            F::R16f => Some(R16Sfloat),
+            F::Rgba16 => Some(R16G16B16A16Unorm),
+            F::Rgb10A2 => Some(A2B10G10R10UnormPack32),
+            F::Rg16 => Some(R16G16Unorm),
+            F::Rg8 => Some(R8G8Unorm),
+            F::R16 => Some(R16Unorm),
+            F::R8 => Some(R8Unorm),
+            F::Rgba16Snorm => Some(R16G16B16A16Snorm),
+            F::Rg16Snorm => Some(R16G16Snorm),
+            F::Rg8Snorm => Some(R8G8Snorm),
+            F::R16Snorm => Some(R16Snorm),
+            F::R8Snorm => Some(R8Snorm),
+            F::Rgba32i => Some(R32G32B32A32Sint),
+            F::Rgba16i => Some(R16G16B16A16Sint),
+            F::Rgba8i => Some(R8G8B8A8Sint),
+            F::R32i => Some(R32Sint),
+            F::Rg32i => Some(R32G32Sint),
+            F::Rg16i => Some(R16G16Sint),
+            F::Rg8i => Some(R8G8Sint),
+            F::R16i => Some(R16Sint),
+            F::R8i => Some(R8Sint),
+            F::Rgba32ui => Some(R32G32B32A32Uint),
+            F::Rgba16ui => Some(R16G16B16A16Uint),
+            F::Rgba8ui => Some(R8G8B8A8Uint),
+            F::R32ui => Some(R32Uint),
+            F::Rgb10a2ui => Some(A2B10G10R10UintPack32),
+            F::Rg32ui => Some(R32G32Uint),
+            F::Rg16ui => Some(R16G16Uint),
+            F::Rg8ui => Some(R8G8Uint),
+            F::R16ui => Some(R16Uint),
+            F::R8ui => Some(R8Uint),
         };
-        SpirvTy { inner };
-        // This function shouldn't be called yet because
-        // it is not implemented correctly
-        unreachable!()
+        SpirvTy { inner }
     }
 }
 
-impl TryFrom<sr::types::ReflectFormat> for SpirvTy<Format> {
+// Matrices take a location per column so they
+// have the format of a column
+impl TryFrom<MemberType> for SpirvTy<Format> {
     type Error = Error;
-    fn try_from(f: sr::types::ReflectFormat) -> Result<Self, Self::Error> {
-        use sr::types::ReflectFormat::*;
+    fn try_from(ty: MemberType) -> Result<Self, Self::Error> {
         use Format::*;
-        let t = match f {
-            Undefined => Err(Error::Layout(ConvertError::Unimplemented(format!("{:?}", f))))?,
-            R32_UINT => R32Uint,
-            R32_SINT => R32Sint,
-            R32_SFLOAT => R32Sfloat,
-            R32G32_UINT => R32G32Uint,
-            R32G32_SINT => R32G32Sint,
-            R32G32_SFLOAT => R32G32Sfloat,
-            R32G32B32_UINT => R32G32B32Uint,
-            R32G32B32_SINT => R32G32B32Sint,
-            R32G32B32_SFLOAT => R32G32B32Sfloat,
-            R32G32B32A32_UINT => R32G32B32A32Uint,
-            R32G32B32A32_SINT => R32G32B32A32Sint,
-            R32G32B32A32_SFLOAT => R32G32B32A32Sfloat,
+        use ScalarType::*;
+        let unimplemented = || Error::Layout(ConvertError::Unimplemented(format!("{:?}", ty)));
+        let (scalar, components) = match ty {
+            MemberType::Scalar(s) => (s, 1),
+            MemberType::Vector(s, n) => (s, n),
+            MemberType::Matrix { ty, rows, .. } => (ty, rows),
+            MemberType::Struct => return Err(unimplemented()),
+        };
+        let t = match (scalar, components) {
+            (Uint(8), 1) => R8Uint,
+            (Sint(8), 1) => R8Sint,
+            (Uint(8), 2) => R8G8Uint,
+            (Sint(8), 2) => R8G8Sint,
+            (Uint(8), 3) => R8G8B8Uint,
+            (Sint(8), 3) => R8G8B8Sint,
+            (Uint(8), 4) => R8G8B8A8Uint,
+            (Sint(8), 4) => R8G8B8A8Sint,
+            (Uint(16), 1) => R16Uint,
+            (Sint(16), 1) => R16Sint,
+            (Float(16), 1) => R16Sfloat,
+            (Uint(16), 2) => R16G16Uint,
+            (Sint(16), 2) => R16G16Sint,
+            (Float(16), 2) => R16G16Sfloat,
+            (Uint(16), 3) => R16G16B16Uint,
+            (Sint(16), 3) => R16G16B16Sint,
+            (Float(16), 3) => R16G16B16Sfloat,
+            (Uint(16), 4) => R16G16B16A16Uint,
+            (Sint(16), 4) => R16G16B16A16Sint,
+            (Float(16), 4) => R16G16B16A16Sfloat,
+            (Uint(32), 1) => R32Uint,
+            (Sint(32), 1) => R32Sint,
+            (Float(32), 1) => R32Sfloat,
+            (Uint(32), 2) => R32G32Uint,
+            (Sint(32), 2) => R32G32Sint,
+            (Float(32), 2) => R32G32Sfloat,
+            (Uint(32), 3) => R32G32B32Uint,
+            (Sint(32), 3) => R32G32B32Sint,
+            (Float(32), 3) => R32G32B32Sfloat,
+            (Uint(32), 4) => R32G32B32A32Uint,
+            (Sint(32), 4) => R32G32B32A32Sint,
+            (Float(32), 4) => R32G32B32A32Sfloat,
+            (Uint(64), 1) => R64Uint,
+            (Sint(64), 1) => R64Sint,
+            (Float(64), 1) => R64Sfloat,
+            (Uint(64), 2) => R64G64Uint,
+            (Sint(64), 2) => R64G64Sint,
+            (Float(64), 2) => R64G64Sfloat,
+            (Uint(64), 3) => R64G64B64Uint,
+            (Sint(64), 3) => R64G64B64Sint,
+            (Float(64), 3) => R64G64B64Sfloat,
+            (Uint(64), 4) => R64G64B64A64Uint,
+            (Sint(64), 4) => R64G64B64A64Sint,
+            (Float(64), 4) => R64G64B64A64Sfloat,
+            _ => return Err(unimplemented()),
         };
         Ok(SpirvTy { inner: t })
     }
@@ -0,0 +1,204 @@
+use crate::error::{Error, OverBudget};
+use crate::spirv;
+use crate::CompiledShaders;
+use spirv_headers as spv;
+
+/// Size and complexity figures for a single shader module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShaderStats {
+    pub words: usize,
+    pub instructions: usize,
+    pub classes: OpcodeClasses,
+    pub functions: usize,
+    pub loops: usize,
+    /// Conditional branches and switches
+    pub branches: usize,
+    /// Sampling, fetching and gathering from images
+    pub texture_samples: usize,
+    /// Variables bound to a descriptor set
+    pub resources: usize,
+}
+
+/// The number of instructions in each group of opcodes. Declarations,
+/// decorations and debug information count as `other`, which includes
+/// variables declared outside of functions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpcodeClasses {
+    /// Arithmetic, derivatives and extended instructions like `GLSL.std.450`
+    pub arithmetic: usize,
+    /// Relational, logical and bit instructions
+    pub logic: usize,
+    pub conversion: usize,
+    pub composite: usize,
+    pub memory: usize,
+    pub image: usize,
+    pub atomic: usize,
+    pub control_flow: usize,
+    pub other: usize,
+}
+
+/// The stats of every stage in a set of compiled shaders.
+/// Stages that weren't compiled are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Statistics {
+    pub vertex: Option<ShaderStats>,
+    pub fragment: Option<ShaderStats>,
+    pub compute: Option<ShaderStats>,
+}
+
+impl Statistics {
+    /// Gathers the stats of each compiled stage.
+    pub fn new(code: &CompiledShaders) -> Result<Self, Error> {
+        let stage = |data: &[u32]| {
+            if data.is_empty() {
+                Ok(None)
+            } else {
+                ShaderStats::new(data).map(Some)
+            }
+        };
+        Ok(Statistics {
+            vertex: stage(&code.vertex)?,
+            fragment: stage(&code.fragment)?,
+            compute: stage(&code.compute)?,
+        })
+    }
+}
+
+impl ShaderStats {
+    pub fn new(data: &[u32]) -> Result<Self, Error> {
+        let module = spirv::Module::new(data)?;
+        let mut stats = ShaderStats {
+            words: data.len(),
+            instructions: module.instructions.len(),
+            ..ShaderStats::default()
+        };
+        let mut in_function = false;
+        for i in &module.instructions {
+            use spv::Op::*;
+            let op = i.op;
+            match op {
+                Function => in_function = true,
+                FunctionEnd => in_function = false,
+                _ => (),
+            }
+            let within = |first: spv::Op, last: spv::Op| (first as u32..=last as u32).contains(&(op as u32));
+            let class = &mut stats.classes;
+            if op == ExtInst || within(SNegate, SMulExtended) || within(DPdx, FwidthCoarse) {
+                class.arithmetic += 1;
+            } else if within(Any, FUnordGreaterThanEqual) || within(ShiftRightLogical, BitCount) {
+                class.logic += 1;
+            } else if within(ConvertFToU, Bitcast) {
+                class.conversion += 1;
+            } else if within(VectorExtractDynamic, Transpose) {
+                class.composite += 1;
+            } else if within(Variable, InBoundsPtrAccessChain) && (op != Variable || in_function) {
+                class.memory += 1;
+            } else if within(SampledImage, ImageQuerySamples)
+                || within(ImageSparseSampleImplicitLod, ImageSparseTexelsResident)
+                || op == ImageSparseRead
+            {
+                class.image += 1;
+            } else if within(AtomicLoad, AtomicXor) || op == AtomicFlagTestAndSet || op == AtomicFlagClear {
+                class.atomic += 1;
+            } else if op == FunctionCall || within(Phi, Unreachable) {
+                class.control_flow += 1;
+            } else {
+                class.other += 1;
+            }
+            match op {
+                Function => stats.functions += 1,
+                LoopMerge => stats.loops += 1,
+                BranchConditional | Switch => stats.branches += 1,
+                _ => (),
+            }
+            if within(ImageSampleImplicitLod, ImageDrefGather) || within(ImageSparseSampleImplicitLod, ImageSparseDrefGather) {
+                stats.texture_samples += 1;
+            }
+            if op == Variable && module.has_decoration(i.operands[1], spv::Decoration::DescriptorSet) {
+                stats.resources += 1;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// Limits on the stats of each stage. Anything left as `None` is unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub words: Option<usize>,
+    pub instructions: Option<usize>,
+    pub functions: Option<usize>,
+    pub loops: Option<usize>,
+    pub branches: Option<usize>,
+    pub texture_samples: Option<usize>,
+    pub resources: Option<usize>,
+}
+
+/// A figure that a `Budget` limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Words,
+    Instructions,
+    Functions,
+    Loops,
+    Branches,
+    TextureSamples,
+    Resources,
+}
+
+/// A compiled stage, for reporting which one went over budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+impl Budget {
+    /// Checks every compiled stage and lists each limit that's exceeded.
+    pub fn check(&self, stats: &Statistics) -> Result<(), Error> {
+        let stages = [
+            (Stage::Vertex, &stats.vertex),
+            (Stage::Fragment, &stats.fragment),
+            (Stage::Compute, &stats.compute),
+        ];
+        let over: Vec<OverBudget> = stages
+            .iter()
+            .filter_map(|(stage, stats)| stats.as_ref().map(|s| (*stage, s)))
+            .flat_map(|(stage, stats)| self.over(stage, stats))
+            .collect();
+        if over.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Budget(over))
+        }
+    }
+
+    /// Gathers the stats of the shaders and checks them.
+    pub fn check_shaders(&self, code: &CompiledShaders) -> Result<(), Error> {
+        self.check(&Statistics::new(code)?)
+    }
+
+    fn over(&self, stage: Stage, stats: &ShaderStats) -> Vec<OverBudget> {
+        let limits = [
+            (Limit::Words, self.words, stats.words),
+            (Limit::Instructions, self.instructions, stats.instructions),
+            (Limit::Functions, self.functions, stats.functions),
+            (Limit::Loops, self.loops, stats.loops),
+            (Limit::Branches, self.branches, stats.branches),
+            (Limit::TextureSamples, self.texture_samples, stats.texture_samples),
+            (Limit::Resources, self.resources, stats.resources),
+        ];
+        limits
+            .iter()
+            .filter_map(|&(limit, max, value)| {
+                max.filter(|&max| value > max).map(|max| OverBudget {
+                    stage,
+                    limit,
+                    value,
+                    max,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,480 @@
+use crate::backend;
+use crate::error::{AttachmentMismatch, Error, InterfaceError, Warning};
+use crate::layouts::Interpolation;
+use crate::reflection;
+use crate::vk::device::{Features, RawDeviceExtensions};
+use crate::vk::format::{Format, FormatTy};
+use crate::vk::pipeline::shader::ShaderInterfaceDefEntry;
+use spirv_headers as spv;
+use std::ffi::CString;
+
+struct Varying {
+    location: u32,
+    component: u32,
+    format: Format,
+    name: String,
+    interpolation: Interpolation,
+    written: bool,
+}
+
+fn varyings(
+    m: &backend::ShaderModule,
+    entry_point: Option<&str>,
+    outputs: bool,
+) -> Result<Vec<Varying>, Error> {
+    let vars = if outputs {
+        m.output_variables(entry_point)?
+    } else {
+        m.input_variables(entry_point)?
+    };
+    let module = m.module();
+    let written = module.written_variables(entry_point);
+    let mut varyings = Vec::new();
+    for v in vars.iter().filter(|v| !v.built_in) {
+        let decorated = |d| module.has_decoration(v.spirv_id, d);
+        let component = module
+            .decoration(v.spirv_id, spv::Decoration::Component)
+            .unwrap_or(0);
+        let interpolation = Interpolation {
+            flat: decorated(spv::Decoration::Flat),
+            no_perspective: decorated(spv::Decoration::NoPerspective),
+            centroid: decorated(spv::Decoration::Centroid),
+            sample: decorated(spv::Decoration::Sample),
+        };
+        // Each location of a matrix, array or struct is matched on its own
+        for entry in reflection::interface_entries(v, v.location, &v.name, false)? {
+            let name = entry.name.map(|n| n.into_owned()).unwrap_or_default();
+            let format = entry.format;
+            varyings.extend(entry.location.map(|location| Varying {
+                location,
+                component,
+                format,
+                name: name.clone(),
+                interpolation,
+                written: written.contains(&v.spirv_id),
+            }));
+        }
+    }
+    Ok(varyings)
+}
+
+// The numeric type and byte width of each component of a format, and how
+// many of a location's four components it takes up. 64 bit values take
+// up two each.
+fn component_layout(format: Format) -> ((FormatTy, usize), u32) {
+    let count = component_count(format).unwrap_or(1);
+    let width = format.size().unwrap_or(0) / count as usize;
+    let slots = if width == 8 { count * 2 } else { count };
+    ((format.ty(), width), slots)
+}
+
+/// Checks that every fragment input is fed by a matching vertex output.
+/// Inputs are matched a component at a time, so one vertex output can
+/// feed several inputs packed into its location and the other way round.
+/// An input alone at its location and fed by a single output must match
+/// that output's format exactly.
+/// Inputs that the vertex stage declares but never writes are only warnings.
+pub fn check_interface(
+    (vertex, vertex_name): (&backend::ShaderModule, Option<&str>),
+    (fragment, fragment_name): (&backend::ShaderModule, Option<&str>),
+) -> Result<Vec<Warning>, Error> {
+    let outputs = varyings(vertex, vertex_name, true)?;
+    let mut inputs = varyings(fragment, fragment_name, false)?;
+    inputs.sort_by_key(|i| (i.location, i.component));
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for input in &inputs {
+        let (scalar, slots) = component_layout(input.format);
+        let end = (input.component + slots).min(4);
+        let feeding = (input.component..end)
+            .map(|c| {
+                outputs.iter().find(|o| {
+                    let (_, slots) = component_layout(o.format);
+                    o.location == input.location && o.component <= c && c < o.component + slots
+                })
+            })
+            .collect::<Option<Vec<_>>>();
+        let feeding = match feeding {
+            Some(f) => f,
+            None => {
+                errors.push(InterfaceError::Missing {
+                    location: input.location,
+                    name: input.name.clone(),
+                });
+                continue;
+            }
+        };
+        // A lone input fed by one output at its own component has to match
+        // it exactly, as vulkano compares the two entries' formats.
+        let lone = inputs
+            .iter()
+            .filter(|i| i.location == input.location)
+            .count()
+            == 1;
+        let single = lone
+            && feeding
+                .iter()
+                .all(|o| std::ptr::eq(*o, feeding[0]) && o.component == input.component);
+        let mismatch = if single {
+            Some(feeding[0]).filter(|o| o.format != input.format)
+        } else {
+            feeding
+                .iter()
+                .copied()
+                .find(|o| component_layout(o.format).0 != scalar)
+        };
+        if let Some(output) = mismatch {
+            errors.push(InterfaceError::Format {
+                location: input.location,
+                vertex: output.format,
+                fragment: input.format,
+            });
+        }
+        if let Some(output) = feeding
+            .iter()
+            .find(|o| o.interpolation != input.interpolation)
+        {
+            errors.push(InterfaceError::Interpolation {
+                location: input.location,
+                vertex: output.interpolation,
+                fragment: input.interpolation,
+            });
+        }
+        if feeding.iter().any(|o| !o.written) {
+            warnings.push(Warning::Unwritten {
+                location: input.location,
+                name: input.name.clone(),
+            });
+        }
+    }
+    if errors.is_empty() {
+        Ok(warnings)
+    } else {
+        Err(Error::Interface(errors))
+    }
+}
+
+/// Compares the fragment outputs with the colour attachments of a subpass,
+/// where the attachment at index `n` is written by location `n`.
+pub fn check_attachments(
+    outputs: &[ShaderInterfaceDefEntry],
+    attachments: &[Format],
+) -> Result<(), Error> {
+    let mut mismatches = Vec::new();
+    for location in 0..attachments.len() as u32 {
+        if !outputs.iter().any(|o| o.location.contains(&location)) {
+            mismatches.push(AttachmentMismatch::Unwritten { location });
+        }
+    }
+    for output in outputs {
+        for location in output.location.clone() {
+            let attachment = match attachments.get(location as usize) {
+                Some(&a) => a,
+                None => {
+                    mismatches.push(AttachmentMismatch::NoAttachment { location });
+                    continue;
+                }
+            };
+            if output.format.ty() != attachment.ty() {
+                mismatches.push(AttachmentMismatch::NumericType {
+                    location,
+                    shader: output.format.ty(),
+                    attachment: attachment.ty(),
+                });
+            }
+            // Formats without a component count can't be compared
+            let shader = component_count(output.format);
+            let attachment = component_count(attachment);
+            if let (Some(shader), Some(attachment)) = (shader, attachment) {
+                if shader < attachment {
+                    mismatches.push(AttachmentMismatch::Components {
+                        location,
+                        shader,
+                        attachment,
+                    });
+                }
+            }
+        }
+    }
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Attachment(mismatches))
+    }
+}
+
+/// Whether vertex data in `format` can feed an input the shader
+/// declares as `shader`. Normalized and scaled formats are read as
+/// floats but integers have to stay integers of the same sign, and
+/// 64 bit inputs need 64 bit data. Formats without a component
+/// count can't be used for vertex data.
+pub fn can_read_as(format: Format, shader: Format) -> bool {
+    let width = |f: Format| Some(f.size()? / component_count(f)? as usize);
+    match (width(format), width(shader)) {
+        (Some(format_width), Some(shader_width)) => {
+            format.ty() == shader.ty() && (format_width == 8) == (shader_width == 8)
+        }
+        _ => false,
+    }
+}
+
+/// The number of components in an uncompressed colour format, which
+/// covers everything an interface or vertex buffer can use. Depth,
+/// stencil and compressed formats give `None`.
+pub fn component_count(format: Format) -> Option<u32> {
+    use Format::*;
+    match format {
+        R8Unorm | R8Snorm | R8Uscaled | R8Sscaled | R8Uint | R8Sint | R8Srgb | R16Unorm
+        | R16Snorm | R16Uscaled | R16Sscaled | R16Uint | R16Sint | R16Sfloat | R32Uint
+        | R32Sint | R32Sfloat | R64Uint | R64Sint | R64Sfloat => Some(1),
+        R4G4UnormPack8 | R8G8Unorm | R8G8Snorm | R8G8Uscaled | R8G8Sscaled | R8G8Uint
+        | R8G8Sint | R8G8Srgb | R16G16Unorm | R16G16Snorm | R16G16Uscaled | R16G16Sscaled
+        | R16G16Uint | R16G16Sint | R16G16Sfloat | R32G32Uint | R32G32Sint | R32G32Sfloat
+        | R64G64Uint | R64G64Sint | R64G64Sfloat => Some(2),
+        R5G6B5UnormPack16
+        | B5G6R5UnormPack16
+        | R8G8B8Unorm
+        | R8G8B8Snorm
+        | R8G8B8Uscaled
+        | R8G8B8Sscaled
+        | R8G8B8Uint
+        | R8G8B8Sint
+        | R8G8B8Srgb
+        | B8G8R8Unorm
+        | B8G8R8Snorm
+        | B8G8R8Uscaled
+        | B8G8R8Sscaled
+        | B8G8R8Uint
+        | B8G8R8Sint
+        | B8G8R8Srgb
+        | R16G16B16Unorm
+        | R16G16B16Snorm
+        | R16G16B16Uscaled
+        | R16G16B16Sscaled
+        | R16G16B16Uint
+        | R16G16B16Sint
+        | R16G16B16Sfloat
+        | R32G32B32Uint
+        | R32G32B32Sint
+        | R32G32B32Sfloat
+        | R64G64B64Uint
+        | R64G64B64Sint
+        | R64G64B64Sfloat
+        | B10G11R11UfloatPack32
+        | E5B9G9R9UfloatPack32 => Some(3),
+        R4G4B4A4UnormPack16
+        | B4G4R4A4UnormPack16
+        | R5G5B5A1UnormPack16
+        | B5G5R5A1UnormPack16
+        | A1R5G5B5UnormPack16
+        | R8G8B8A8Unorm
+        | R8G8B8A8Snorm
+        | R8G8B8A8Uscaled
+        | R8G8B8A8Sscaled
+        | R8G8B8A8Uint
+        | R8G8B8A8Sint
+        | R8G8B8A8Srgb
+        | B8G8R8A8Unorm
+        | B8G8R8A8Snorm
+        | B8G8R8A8Uscaled
+        | B8G8R8A8Sscaled
+        | B8G8R8A8Uint
+        | B8G8R8A8Sint
+        | B8G8R8A8Srgb
+        | A8B8G8R8UnormPack32
+        | A8B8G8R8SnormPack32
+        | A8B8G8R8UscaledPack32
+        | A8B8G8R8SscaledPack32
+        | A8B8G8R8UintPack32
+        | A8B8G8R8SintPack32
+        | A8B8G8R8SrgbPack32
+        | A2R10G10B10UnormPack32
+        | A2R10G10B10SnormPack32
+        | A2R10G10B10UscaledPack32
+        | A2R10G10B10SscaledPack32
+        | A2R10G10B10UintPack32
+        | A2R10G10B10SintPack32
+        | A2B10G10R10UnormPack32
+        | A2B10G10R10SnormPack32
+        | A2B10G10R10UscaledPack32
+        | A2B10G10R10SscaledPack32
+        | A2B10G10R10UintPack32
+        | A2B10G10R10SintPack32
+        | R16G16B16A16Unorm
+        | R16G16B16A16Snorm
+        | R16G16B16A16Uscaled
+        | R16G16B16A16Sscaled
+        | R16G16B16A16Uint
+        | R16G16B16A16Sint
+        | R16G16B16A16Sfloat
+        | R32G32B32A32Uint
+        | R32G32B32A32Sint
+        | R32G32B32A32Sfloat
+        | R64G64B64A64Uint
+        | R64G64B64A64Sint
+        | R64G64B64A64Sfloat => Some(4),
+        _ => None,
+    }
+}
+
+/// The device features needed by a module declaring `capabilities`.
+/// Capabilities that are part of core Vulkan don't add anything. Ones
+/// whose features vulkano's `Features` doesn't have, like the 16 and 8
+/// bit storage ones, need their extension from `required_extensions`.
+pub fn required_features(capabilities: &[spv::Capability]) -> Features {
+    use spv::Capability::*;
+    let mut features = Features::none();
+    for capability in capabilities {
+        match capability {
+            Geometry => features.geometry_shader = true,
+            Tessellation => features.tessellation_shader = true,
+            Float64 => features.shader_f3264 = true,
+            Int64 | Int64Atomics => features.shader_int64 = true,
+            Int16 => features.shader_int16 = true,
+            TessellationPointSize | GeometryPointSize => {
+                features.shader_tessellation_and_geometry_point_size = true
+            }
+            ImageGatherExtended => features.shader_image_gather_extended = true,
+            StorageImageMultisample => features.shader_storage_image_multisample = true,
+            UniformBufferArrayDynamicIndexing => {
+                features.shader_uniform_buffer_array_dynamic_indexing = true
+            }
+            SampledImageArrayDynamicIndexing => {
+                features.shader_sampled_image_array_dynamic_indexing = true
+            }
+            StorageBufferArrayDynamicIndexing => {
+                features.shader_storage_buffer_array_dynamic_indexing = true
+            }
+            StorageImageArrayDynamicIndexing => {
+                features.shader_storage_image_array_dynamic_indexing = true
+            }
+            ClipDistance => features.shader_clip_distance = true,
+            CullDistance => features.shader_cull_distance = true,
+            ImageCubeArray | SampledCubeArray => features.image_cube_array = true,
+            SampleRateShading => features.sample_rate_shading = true,
+            SparseResidency => features.shader_resource_residency = true,
+            MinLod => features.shader_resource_min_lod = true,
+            StorageImageExtendedFormats => features.shader_storage_image_extended_formats = true,
+            StorageImageReadWithoutFormat => {
+                features.shader_storage_image_read_without_format = true
+            }
+            StorageImageWriteWithoutFormat => {
+                features.shader_storage_image_write_without_format = true
+            }
+            MultiViewport => features.multi_viewport = true,
+            _ => (),
+        }
+    }
+    features
+}
+
+/// The device extensions needed by a module declaring the SPIR-V
+/// `extensions`. vulkano creates Vulkan 1.0 instances unless told
+/// otherwise so extensions Vulkan 1.1 made core still need theirs.
+/// Ones that only carry information for tools, like
+/// `SPV_GOOGLE_user_type`, and any not listed here don't add anything.
+/// Modules for SPIR-V 1.3 and later can leave out extensions that became
+/// core SPIR-V so `capabilities` that need one add it too.
+pub fn required_extensions(
+    extensions: &[String],
+    capabilities: &[spv::Capability],
+) -> RawDeviceExtensions {
+    let from_extensions = extensions.iter().filter_map(|e| {
+        let name = match e.as_str() {
+            "SPV_KHR_16bit_storage" => "VK_KHR_16bit_storage",
+            "SPV_KHR_storage_buffer_storage_class" => "VK_KHR_storage_buffer_storage_class",
+            "SPV_KHR_multiview" => "VK_KHR_multiview",
+            "SPV_KHR_variable_pointers" => "VK_KHR_variable_pointers",
+            "SPV_KHR_shader_draw_parameters" => "VK_KHR_shader_draw_parameters",
+            "SPV_KHR_device_group" => "VK_KHR_device_group",
+            "SPV_KHR_shader_ballot" => "VK_EXT_shader_subgroup_ballot",
+            "SPV_KHR_subgroup_vote" => "VK_EXT_shader_subgroup_vote",
+            "SPV_KHR_8bit_storage" => "VK_KHR_8bit_storage",
+            "SPV_KHR_float_controls" => "VK_KHR_shader_float_controls",
+            "SPV_KHR_non_semantic_info" => "VK_KHR_shader_non_semantic_info",
+            "SPV_KHR_post_depth_coverage" => "VK_EXT_post_depth_coverage",
+            "SPV_KHR_vulkan_memory_model" => "VK_KHR_vulkan_memory_model",
+            "SPV_KHR_shader_clock" => "VK_KHR_shader_clock",
+            "SPV_KHR_physical_storage_buffer" => "VK_KHR_buffer_device_address",
+            "SPV_KHR_fragment_shading_rate" => "VK_KHR_fragment_shading_rate",
+            "SPV_KHR_terminate_invocation" => "VK_KHR_shader_terminate_invocation",
+            "SPV_KHR_ray_tracing" => "VK_KHR_ray_tracing_pipeline",
+            "SPV_KHR_ray_query" => "VK_KHR_ray_query",
+            "SPV_EXT_physical_storage_buffer" => "VK_EXT_buffer_device_address",
+            "SPV_EXT_descriptor_indexing" => "VK_EXT_descriptor_indexing",
+            "SPV_EXT_shader_stencil_export" => "VK_EXT_shader_stencil_export",
+            "SPV_EXT_shader_viewport_index_layer" => "VK_EXT_shader_viewport_index_layer",
+            "SPV_EXT_fragment_fully_covered" => "VK_EXT_conservative_rasterization",
+            "SPV_EXT_fragment_invocation_density" => "VK_EXT_fragment_density_map",
+            "SPV_EXT_fragment_shader_interlock" => "VK_EXT_fragment_shader_interlock",
+            "SPV_EXT_demote_to_helper_invocation" => "VK_EXT_shader_demote_to_helper_invocation",
+            "SPV_EXT_shader_atomic_float_add" => "VK_EXT_shader_atomic_float",
+            "SPV_AMD_gcn_shader" => "VK_AMD_gcn_shader",
+            "SPV_AMD_gpu_shader_half_float" => "VK_AMD_gpu_shader_half_float",
+            "SPV_AMD_gpu_shader_int16" => "VK_AMD_gpu_shader_int16",
+            "SPV_AMD_shader_ballot" => "VK_AMD_shader_ballot",
+            "SPV_AMD_shader_explicit_vertex_parameter" => "VK_AMD_shader_explicit_vertex_parameter",
+            "SPV_AMD_shader_fragment_mask" => "VK_AMD_shader_fragment_mask",
+            "SPV_AMD_shader_image_load_store_lod" => "VK_AMD_shader_image_load_store_lod",
+            "SPV_AMD_shader_trinary_minmax" => "VK_AMD_shader_trinary_minmax",
+            "SPV_AMD_texture_gather_bias_lod" => "VK_AMD_texture_gather_bias_lod",
+            "SPV_NV_compute_shader_derivatives" => "VK_NV_compute_shader_derivatives",
+            "SPV_NV_cooperative_matrix" => "VK_NV_cooperative_matrix",
+            "SPV_NV_fragment_shader_barycentric" => "VK_NV_fragment_shader_barycentric",
+            "SPV_NV_geometry_shader_passthrough" => "VK_NV_geometry_shader_passthrough",
+            "SPV_NV_mesh_shader" => "VK_NV_mesh_shader",
+            "SPV_NV_ray_tracing" => "VK_NV_ray_tracing",
+            "SPV_NV_sample_mask_override_coverage" => "VK_NV_sample_mask_override_coverage",
+            "SPV_NV_shader_image_footprint" => "VK_NV_shader_image_footprint",
+            "SPV_NV_shader_sm_builtins" => "VK_NV_shader_sm_builtins",
+            "SPV_NV_shader_subgroup_partitioned" => "VK_NV_shader_subgroup_partitioned",
+            "SPV_NV_shading_rate" => "VK_NV_shading_rate_image",
+            "SPV_NV_viewport_array2" => "VK_NV_viewport_array2",
+            "SPV_NVX_multiview_per_view_attributes" => "VK_NVX_multiview_per_view_attributes",
+            _ => return None,
+        };
+        Some(name)
+    });
+    let from_capabilities = capabilities.iter().filter_map(|c| {
+        use spv::Capability::*;
+        let name = match c {
+            StorageBuffer16BitAccess
+            | UniformAndStorageBuffer16BitAccess
+            | StoragePushConstant16
+            | StorageInputOutput16 => "VK_KHR_16bit_storage",
+            StorageBuffer8BitAccess | UniformAndStorageBuffer8BitAccess | StoragePushConstant8 => {
+                "VK_KHR_8bit_storage"
+            }
+            Float16 | Int8 => "VK_KHR_shader_float16_int8",
+            DrawParameters => "VK_KHR_shader_draw_parameters",
+            MultiView => "VK_KHR_multiview",
+            VariablePointersStorageBuffer | VariablePointers => "VK_KHR_variable_pointers",
+            _ => return None,
+        };
+        Some(name)
+    });
+    from_extensions
+        .chain(from_capabilities)
+        .filter_map(|name| CString::new(name).ok())
+        .collect()
+}
+
+/// Checks a device enables every feature and extension the shader needs,
+/// giving the ones that are missing otherwise.
+pub fn check_device(
+    required_features: &Features,
+    required_extensions: &RawDeviceExtensions,
+    features: &Features,
+    extensions: &RawDeviceExtensions,
+) -> Result<(), Error> {
+    let missing_features = required_features.difference(features);
+    let missing_extensions = required_extensions.difference(extensions);
+    if missing_features == Features::none() && missing_extensions.iter().next().is_none() {
+        Ok(())
+    } else {
+        Err(Error::Device {
+            features: missing_features,
+            extensions: missing_extensions,
+        })
+    }
+}
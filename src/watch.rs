@@ -1,5 +1,7 @@
 use crate::error::Error;
-use crate::layouts::Entry;
+use crate::layouts::{Entry, RuntimeArrays};
+use crate::stats::Budget;
+use crate::vk::format::Format;
 use crate::CompiledShaders;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use std::path::{Path, PathBuf};
@@ -19,18 +21,32 @@ enum Loader {
 }
 
 enum SrcPath {
-    Graphics(PathBuf, PathBuf),
-    Compute(PathBuf),
+    Graphics(PathBuf, PathBuf, Checks),
+    Compute(PathBuf, Checks),
+}
+
+/// Extra checks run on every reload. A reload
+/// that fails one is sent as an error.
+#[derive(Debug, Clone, Default)]
+pub struct Checks {
+    /// The colour attachment formats the fragment outputs have to match
+    pub attachments: Option<Vec<Format>>,
+    /// Limits on the size and complexity of each stage
+    pub budget: Option<Budget>,
+    /// Counts for runtime sized descriptor arrays
+    pub arrays: RuntimeArrays,
 }
 
 struct GraphicsLoader {
     vertex: PathBuf,
     fragment: PathBuf,
+    checks: Checks,
     tx: Sender<Result<Message, Error>>,
 }
 
 struct ComputeLoader {
     compute: PathBuf,
+    checks: Checks,
     tx: Sender<Result<Message, Error>>,
 }
 
@@ -43,12 +59,44 @@ impl Watch {
     /// Paths to the vertex and fragment shaders.
     /// Frequency is how often the watcher will check the directory.
     pub fn create<T>(vertex: T, fragment: T, frequency: Duration) -> Result<Self, Error>
+    where
+        T: AsRef<Path>,
+    {
+        Watch::create_with_checks(vertex, fragment, frequency, Checks::default())
+    }
+
+    /// Same as `create` but reloads whose fragment outputs don't
+    /// match the colour attachment formats are sent as errors.
+    pub fn create_with_attachments<T>(
+        vertex: T,
+        fragment: T,
+        frequency: Duration,
+        attachments: Vec<Format>,
+    ) -> Result<Self, Error>
+    where
+        T: AsRef<Path>,
+    {
+        let checks = Checks {
+            attachments: Some(attachments),
+            ..Checks::default()
+        };
+        Watch::create_with_checks(vertex, fragment, frequency, checks)
+    }
+
+    /// Same as `create` but every reload has to pass `checks`.
+    pub fn create_with_checks<T>(
+        vertex: T,
+        fragment: T,
+        frequency: Duration,
+        checks: Checks,
+    ) -> Result<Self, Error>
     where
         T: AsRef<Path>,
     {
         let src_path = SrcPath::Graphics(
             vertex.as_ref().to_path_buf(),
-            fragment.as_ref().to_path_buf()
+            fragment.as_ref().to_path_buf(),
+            checks,
             );
         let (handler, rx) = create_watch(
             src_path,
@@ -61,11 +109,21 @@ impl Watch {
     }
 
     pub fn create_compute<T>(compute: T, frequency: Duration) -> Result<Self, Error>
+    where
+        T: AsRef<Path>,
+    {
+        Watch::create_compute_with_checks(compute, frequency, Checks::default())
+    }
+
+    /// Same as `create_compute` but every reload has to pass the
+    /// budget in `checks` and gets its runtime array counts from it.
+    /// Attachments don't apply to compute shaders.
+    pub fn create_compute_with_checks<T>(compute: T, frequency: Duration, checks: Checks) -> Result<Self, Error>
     where
         T: AsRef<Path>,
     {
         let src_path = SrcPath::Compute(
-            compute.as_ref(). to_path_buf());
+            compute.as_ref(). to_path_buf(), checks);
         let (handler, rx) = create_watch(
             src_path,
             frequency,
@@ -78,11 +136,16 @@ impl Watch {
 }
 
 impl GraphicsLoader {
-    fn create(vertex: PathBuf, fragment: PathBuf) -> (Self, Receiver<Result<Message, Error>>) {
+    fn create(
+        vertex: PathBuf,
+        fragment: PathBuf,
+        checks: Checks,
+    ) -> (Self, Receiver<Result<Message, Error>>) {
         let (tx, rx) = mpsc::channel();
         let loader = GraphicsLoader {
             vertex,
             fragment,
+            checks,
             tx,
         };
         loader.reload();
@@ -92,7 +155,12 @@ impl GraphicsLoader {
     fn reload(&self) {
         match crate::load(&self.vertex, &self.fragment) {
             Ok(shaders) => {
-                let entry = crate::parse(&shaders);
+                let entry = crate::parse_with_arrays(&shaders, &self.checks.arrays)
+                    .and_then(|entry| match &self.checks.attachments {
+                        Some(a) => entry.frag_output.check_attachments(a).map(|_| entry),
+                        None => Ok(entry),
+                    });
+                let entry = entry.and_then(|entry| self.checks.check_budget(&shaders).map(|_| entry));
                 let msg = entry.map(|entry| Message { shaders, entry });
                 self.tx.send(msg).ok()
             }
@@ -102,10 +170,11 @@ impl GraphicsLoader {
 }
 
 impl ComputeLoader {
-    fn create(compute: PathBuf) -> (Self, Receiver<Result<Message, Error>>) {
+    fn create(compute: PathBuf, checks: Checks) -> (Self, Receiver<Result<Message, Error>>) {
         let (tx, rx) = mpsc::channel();
         let loader = ComputeLoader {
             compute,
+            checks,
             tx,
         };
         loader.reload();
@@ -115,7 +184,8 @@ impl ComputeLoader {
     fn reload(&self) {
         match crate::load_compute(&self.compute) {
             Ok(shaders) => {
-                let entry = crate::parse_compute(&shaders);
+                let entry = crate::parse_compute_with_arrays(&shaders, &self.checks.arrays)
+                    .and_then(|entry| self.checks.check_budget(&shaders).map(|_| entry));
                 let msg = entry.map(|entry| Message { shaders, entry });
                 self.tx.send(msg).ok()
             }
@@ -124,6 +194,15 @@ impl ComputeLoader {
     }
 }
 
+impl Checks {
+    fn check_budget(&self, shaders: &CompiledShaders) -> Result<(), Error> {
+        match &self.budget {
+            Some(b) => b.check_shaders(shaders),
+            None => Ok(()),
+        }
+    }
+}
+
 impl Loader {
     fn reload(&self) {
         match self {
@@ -158,7 +237,7 @@ fn create_watch(
         Watcher::new(notify_tx, frequency).map_err(Error::FileWatch)?;
 
     let (loader, rx) = match src_path {
-        SrcPath::Graphics(vert_path, frag_path) => {
+        SrcPath::Graphics(vert_path, frag_path, checks) => {
             let mut vp = vert_path.clone();
             let mut fp = frag_path.clone();
             vp.pop();
@@ -172,17 +251,17 @@ fn create_watch(
                     .map_err(Error::FileWatch)?;
             }
 
-            let (loader, rx) = GraphicsLoader::create(vert_path, frag_path);
+            let (loader, rx) = GraphicsLoader::create(vert_path, frag_path, checks);
             (Loader::Graphics(loader), rx)
         }
-        SrcPath::Compute(compute_path) => {
+        SrcPath::Compute(compute_path, checks) => {
             let mut cp = compute_path.clone();
             cp.pop();
             watcher
                 .watch(&cp, RecursiveMode::NonRecursive)
                 .map_err(Error::FileWatch)?;
 
-            let (loader, rx) = ComputeLoader::create(compute_path);
+            let (loader, rx) = ComputeLoader::create(compute_path, checks);
             (Loader::Compute(loader), rx)
         }
     };
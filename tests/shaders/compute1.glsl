@@ -0,0 +1,14 @@
+#version 450
+
+layout(local_size_x = 8, local_size_y = 8) in;
+
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D src;
+layout(set = 0, binding = 1, r32f) uniform image2D dst;
+layout(set = 0, binding = 2) uniform writeonly image2D unknown;
+
+void main() {
+  ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+  vec4 c = imageLoad(src, p) + imageLoad(dst, p);
+  imageStore(dst, p, c);
+  imageStore(unknown, p, c);
+}
@@ -0,0 +1,11 @@
+#version 450
+
+layout(local_size_x = 64) in;
+
+layout(set = 0, binding = 0) uniform samplerBuffer weights;
+layout(set = 0, binding = 1, rgba16f) uniform imageBuffer particles;
+
+void main() {
+  int i = int(gl_GlobalInvocationID.x);
+  imageStore(particles, i, imageLoad(particles, i) * texelFetch(weights, i));
+}
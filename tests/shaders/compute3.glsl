@@ -0,0 +1,25 @@
+#version 450
+
+layout(local_size_x = 64) in;
+
+layout(set = 0, binding = 0) uniform Params {
+  float scale;
+};
+layout(set = 0, binding = 1) readonly buffer Input {
+  float values[];
+} src;
+layout(set = 0, binding = 2) writeonly buffer Output {
+  float values[];
+} dst;
+layout(set = 0, binding = 3) buffer Counter {
+  readonly uint limit;
+  uint count;
+};
+
+void main() {
+  uint i = gl_GlobalInvocationID.x;
+  dst.values[i] = src.values[i] * scale;
+  if (i < limit) {
+    atomicAdd(count, 1);
+  }
+}
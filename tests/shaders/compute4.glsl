@@ -0,0 +1,19 @@
+#version 450
+
+layout(local_size_x = 64) in;
+
+layout(constant_id = 0) const bool use_scale = true;
+layout(constant_id = 1) const int offset = -3;
+layout(constant_id = 2) const uint count = 16;
+layout(constant_id = 5) const float scale = 0.5;
+
+layout(set = 0, binding = 0) buffer Data {
+  float values[];
+};
+
+void main() {
+  uint i = gl_GlobalInvocationID.x;
+  if (use_scale && i < count) {
+    values[i] = values[i + offset] * scale;
+  }
+}
@@ -0,0 +1,10 @@
+#version 450
+
+layout(local_size_x = 16, local_size_x_id = 0, local_size_y = 4) in;
+
+layout(set = 0, binding = 0, r32f) uniform image2D image;
+
+void main() {
+  ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+  imageStore(image, pixel, vec4(gl_WorkGroupSize.x));
+}
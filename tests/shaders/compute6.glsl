@@ -0,0 +1,15 @@
+#version 450
+#extension GL_ARB_gpu_shader_int64 : require
+#extension GL_ARB_shader_ballot : require
+
+layout(local_size_x = 64) in;
+
+layout(set = 0, binding = 0) writeonly uniform image2D image;
+layout(set = 0, binding = 1) buffer Counts {
+  uint64_t lanes;
+};
+
+void main() {
+  lanes = ballotARB(true);
+  imageStore(image, ivec2(gl_GlobalInvocationID.xy), vec4(1.0));
+}
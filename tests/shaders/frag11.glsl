@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) in vec3 color;
+layout(location = 1) flat in vec2 uv;
+layout(location = 3) in float fade;
+layout(location = 0) out vec4 f_color;
+
+void main() {
+  f_color = vec4(color, fade) + vec4(uv, 0.0, 0.0);
+}
@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+layout(location = 1) out ivec2 f_id;
+
+void main() {
+  f_color = vec4(1.0);
+  f_id = ivec2(1, 2);
+}
@@ -0,0 +1,12 @@
+#version 450
+
+struct Light { vec4 color; vec2 dir; };
+layout(location = 0) in vec4 weights[3];
+layout(location = 3) in Light light;
+layout(location = 5) in Block { vec3 a; mat2 b; } blk;
+layout(location = 0) out vec4 f_color;
+
+void main() {
+  f_color = weights[0] + weights[1] + weights[2] + light.color;
+  f_color.xy += light.dir + blk.b[1] + blk.a.xy;
+}
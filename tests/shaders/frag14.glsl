@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0, component = 2) in vec2 offset;
+layout(location = 0) in vec2 uv;
+layout(location = 1, component = 1) in float fade;
+layout(location = 0) out vec4 f_color;
+
+void main() {
+  f_color = vec4(uv + offset, fade, 1.0);
+}
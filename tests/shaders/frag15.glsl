@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) in float offset;
+layout(location = 0) out vec4 f_color;
+
+void main() {
+  f_color = vec4(uv, offset, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 450
+#extension GL_EXT_nonuniform_qualifier : require
+
+layout(location = 0) in flat uint index;
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler samp;
+layout(set = 1, binding = 0) uniform texture2D textures[];
+layout(set = 1, binding = 1) buffer Lights {
+  vec4 colors[];
+};
+
+void main() {
+  f_color = texture(sampler2D(textures[nonuniformEXT(index)], samp), vec2(0.5)) * colors[index];
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 2, binding = 0) uniform sampler2D tex;
+layout(set = 2, binding = 5) uniform Tint {
+  vec4 color;
+} tint;
+
+void main() {
+  f_color = texture(tex, vec2(0.5)) * tint.color;
+}
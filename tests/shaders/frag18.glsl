@@ -0,0 +1,16 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2DArrayShadow shadows;
+layout(set = 0, binding = 1) uniform samplerCubeArray sky;
+layout(set = 0, binding = 2) uniform usampler2D ids;
+layout(set = 0, binding = 3) uniform isampler2DRect offsets;
+
+void main() {
+  float lit = texture(shadows, vec4(0.5, 0.5, 1.0, 0.5));
+  vec4 sky_color = texture(sky, vec4(1.0, 0.0, 0.0, 2.0));
+  uint id = texture(ids, vec2(0.5)).r;
+  int offset = texture(offsets, vec2(4.0)).r;
+  f_color = sky_color * lit + vec4(float(id), float(offset), 0.0, 0.0);
+}
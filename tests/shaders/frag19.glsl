@@ -0,0 +1,25 @@
+#version 450
+
+struct Light {
+  vec3 dir;
+  float power;
+};
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Globals {
+  layout(row_major) mat4 view;
+  mat3 normal;
+  Light lights[2];
+  vec2 scale;
+  int count;
+} globals;
+
+layout(set = 0, binding = 1) buffer Particles {
+  float weight;
+  vec4 positions[];
+} particles;
+
+void main() {
+  f_color = globals.view[0] + vec4(globals.lights[1].dir, globals.scale.x) + particles.positions[2];
+}
@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+struct Wave {
+  float amplitude;
+  float frequency;
+};
+
+layout(push_constant) uniform PushConstantData {
+  float time;
+  Wave wave;
+  vec4 tint;
+} pc;
+
+void main() {
+  f_color = pc.tint * sin(pc.time * pc.wave.frequency) * pc.wave.amplitude;
+}
@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) flat in int instance;
+layout(location = 0) out vec4 f_color;
+
+void main() {
+  f_color = vec4(float(instance), float(gl_SampleID), gl_FragCoord.xy);
+  gl_FragDepth = gl_FragCoord.z;
+}
@@ -0,0 +1,9 @@
+#version 450
+
+layout(early_fragment_tests) in;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+  f_color = gl_FragCoord;
+}
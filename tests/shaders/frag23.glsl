@@ -0,0 +1,10 @@
+#version 450
+
+layout(depth_greater) out float gl_FragDepth;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+  f_color = vec4(1.0);
+  gl_FragDepth = gl_FragCoord.z + 0.1;
+}
@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Camera {
+  mat4 view;
+} camera;
+layout(set = 1, binding = 0) uniform sampler2D albedo;
+layout(set = 1, binding = 3) uniform Material {
+  vec4 colour;
+} u_material;
+layout(set = 2, binding = 0) uniform Lights {
+  vec4 sun;
+};
+
+void main() {
+  f_color = texture(albedo, vec2(0.5)) * u_material.colour * sun * camera.view[0];
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D albedo;
+layout(set = 0, binding = 1) uniform sampler2D detail;
+
+void main() {
+  vec4 colour = vec4(0.0);
+  for (int i = 0; i < 4; i++) {
+    colour += texture(albedo, vec2(float(i) * 0.25));
+  }
+  if (colour.a > 0.5) {
+    colour *= texture(detail, gl_FragCoord.xy);
+  }
+  f_color = colour;
+}
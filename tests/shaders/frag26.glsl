@@ -0,0 +1,27 @@
+#version 450
+
+// Only read by the backend comparison in `backend.rs`. It covers the
+// parts of spirv-reflect's sizes the other shaders don't.
+
+layout(location = 0) out vec4 f_color;
+
+struct Light {
+  vec3 position;
+  float radius;
+  vec2 falloff;
+};
+
+layout(set = 0, binding = 0) uniform sampler2D shadows[2][3];
+layout(set = 0, binding = 1) buffer Lights {
+  Light key;
+  Light fill[];
+} lights;
+layout(set = 0, binding = 2) uniform Sun {
+  Light light;
+  float intensity;
+} sun;
+
+void main() {
+  float radius = lights.key.radius + lights.fill[1].radius + sun.light.radius * sun.intensity;
+  f_color = texture(shadows[1][2], vec2(0.5)) * radius;
+}
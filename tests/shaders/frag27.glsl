@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec2 rg;
+layout(location = 0, component = 2) in vec2 ba;
+layout(location = 1) in float fade;
+layout(location = 1, component = 2) in vec2 offset;
+layout(location = 2) in vec2 pairs[2];
+layout(location = 3, component = 2) in float extra;
+layout(location = 0) out vec4 f_color;
+
+void main() {
+  f_color = vec4(rg + ba + offset + pairs[0] + pairs[1], fade, extra);
+}
@@ -0,0 +1,14 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Globals {
+    vec4 tint;
+    vec2 offset;
+} globals;
+
+layout(set = 1, binding = 0) uniform sampler2D tex;
+
+void main() {
+  f_color = texture(tex, globals.offset) * globals.tint;
+}
@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D tex;
+
+void main() {
+  f_color = texture(tex, vec2(0.0, 0.5));
+}
@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform PushConstants {
+    mat4 transform;
+    vec4 tint;
+    float time;
+} pc;
+
+void main() {
+  f_color = pc.tint;
+}
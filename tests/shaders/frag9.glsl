@@ -0,0 +1,11 @@
+#version 450
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform PushConstants {
+    vec4 tint;
+} pc;
+
+void main() {
+  f_color = pc.tint;
+}
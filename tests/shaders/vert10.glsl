@@ -0,0 +1,14 @@
+#version 450
+#extension GL_EXT_shader_16bit_storage : require
+#extension GL_EXT_shader_explicit_arithmetic_types : require
+
+layout(location = 0) in dvec3 position;
+layout(location = 2) in f16vec2 uv;
+layout(location = 3) in i16vec4 ids;
+layout(location = 4) in double weight;
+layout(location = 5) in dmat2x3 offsets;
+
+void main() {
+  vec2 p = vec2(uv) + vec2(ids.xy) + vec2(weight) + vec2(offsets[1].xy);
+  gl_Position = vec4(vec3(position), 1.0) + vec4(p, 0.0, 0.0);
+}
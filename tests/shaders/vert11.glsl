@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+layout(location = 0) out vec2 uv;
+layout(location = 0, component = 2) out vec2 offset;
+layout(location = 1, component = 1) out float fade;
+
+void main() {
+  uv = position;
+  offset = position * 0.5;
+  fade = 1.0;
+  gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+layout(location = 0) out flat uint index;
+
+void main() {
+  index = gl_InstanceIndex;
+  gl_Position = vec4(position, 0.0, 1.0);
+}
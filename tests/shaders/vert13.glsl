@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+
+struct Wave {
+  float amplitude;
+  float frequency;
+};
+
+layout(push_constant) uniform PushConstantData {
+  float time;
+  Wave wave;
+  vec4 tint;
+} pc;
+
+void main() {
+  gl_Position = vec4(position * pc.time, 0.0, 1.0);
+}
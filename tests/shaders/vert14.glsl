@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+layout(location = 0) flat out int instance;
+
+void main() {
+  instance = gl_InstanceIndex;
+  gl_Position = vec4(position, 0.0, 1.0);
+  gl_PointSize = 2.0;
+}
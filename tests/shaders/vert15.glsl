@@ -0,0 +1,11 @@
+#version 450
+
+layout(location = 0) in vec4 position;
+
+layout(set = 0, binding = 0) uniform Camera {
+  mat4 view;
+} camera;
+
+void main() {
+  gl_Position = camera.view * position;
+}
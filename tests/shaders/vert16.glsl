@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+layout(location = 0) out vec4 color;
+layout(location = 1) out float fade;
+layout(location = 1, component = 2) out vec2 offset;
+layout(location = 2) out vec2 pairs[2];
+layout(location = 3, component = 2) out float extra;
+
+void main() {
+  color = vec4(position, 0.0, 1.0);
+  fade = 0.5;
+  offset = position;
+  pairs[0] = position;
+  pairs[1] = position * 2.0;
+  extra = 1.0;
+  gl_Position = vec4(position, 0.0, 1.0);
+}
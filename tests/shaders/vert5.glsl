@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+
+layout(set = 0, binding = 0) uniform Used {
+    vec2 offset;
+} used;
+
+layout(set = 0, binding = 1) uniform Unused {
+    vec2 offset;
+} unused;
+
+void main() {
+  gl_Position = vec4(position + used.offset, 0.0, 1.0);
+}
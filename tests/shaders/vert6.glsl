@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+
+layout(set = 0, binding = 0) uniform Globals {
+    vec4 tint;
+    vec2 offset;
+} globals;
+
+void main() {
+  gl_Position = vec4(position + globals.offset, 0.0, 1.0);
+}
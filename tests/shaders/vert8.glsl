@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+layout(location = 0) out vec4 color;
+layout(location = 1) out vec2 uv;
+
+void main() {
+  color = vec4(position, 0.0, 1.0);
+  gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+struct Light { vec4 color; vec2 dir; };
+layout(location = 0) in vec2 position;
+layout(location = 1) in mat4 transform;
+layout(location = 5) in vec3 normal[2];
+layout(location = 0) out vec4 weights[3];
+layout(location = 3) out Light light;
+layout(location = 5) out Block { vec3 a; mat2 b; } blk;
+
+void main() {
+  weights[0] = vec4(1.0);
+  weights[1] = vec4(normal[1], 1.0);
+  weights[2] = transform[0];
+  light.color = vec4(1.0);
+  light.dir = position;
+  blk.a = vec3(1.0);
+  blk.b = mat2(1.0);
+  gl_Position = transform * vec4(position, 0.0, 1.0);
+}
@@ -3,11 +3,14 @@ use difference::{Changeset, Difference};
 use shade_runner::*;
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ffi::CString;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use vulkano::descriptor::descriptor::*;
+use vulkano::device::{DeviceExtensions, Features, RawDeviceExtensions};
 use vulkano::descriptor::pipeline_layout::{PipelineLayoutDesc, PipelineLayoutDescPcRange};
 use vulkano::format::*;
-use vulkano::pipeline::shader::ShaderInterfaceDefEntry;
+use vulkano::pipeline::shader::{ShaderInterfaceDefEntry, SpecializationConstants};
 
 fn setup() {
     color_backtrace::install();
@@ -41,7 +44,7 @@ where
     for n in 0..num_sets {
         let num_bindings = desc.num_bindings_in_set(n);
         r = format!("{:?}{:?}", r, num_bindings);
-        for b in num_bindings {
+        for b in 0..num_bindings.unwrap_or(0) {
             r = format!("{:?}{:?}", r, desc.descriptor(n, b));
         }
     }
@@ -53,7 +56,7 @@ where
     r
 }
 
-fn parse<T>(vertex: T, fragment: T) -> shade_runner::Entry
+fn compile<T>(vertex: T, fragment: T) -> CompiledShaders
 where
     T: AsRef<Path>,
 {
@@ -64,8 +67,14 @@ where
     vertex_path.push(vertex);
     let mut fragment_path = path.clone();
     fragment_path.push(fragment);
-    let shader = shade_runner::load(vertex_path, fragment_path).expect("Failed to compile");
-    shade_runner::parse(&shader).unwrap()
+    shade_runner::load(vertex_path, fragment_path).expect("Failed to compile")
+}
+
+fn parse<T>(vertex: T, fragment: T) -> shade_runner::Entry
+where
+    T: AsRef<Path>,
+{
+    shade_runner::parse(&compile(vertex, fragment)).unwrap()
 }
 
 fn do_test<T>(a: &T, b: &T)
@@ -82,6 +91,26 @@ fn test_shade1() {
     setup();
     let target = Entry {
         compute_layout: Default::default(),
+        vert_info: StageInfo {
+            capabilities: vec![Capability::Shader],
+            extensions: Vec::new(),
+            built_ins_read: Vec::new(),
+            built_ins_written: vec![BuiltIn::Position],
+            execution_modes: Default::default(),
+        },
+        frag_info: StageInfo {
+            capabilities: vec![Capability::Shader],
+            extensions: Vec::new(),
+            built_ins_read: Vec::new(),
+            built_ins_written: Vec::new(),
+            execution_modes: ExecutionModes {
+                origin_upper_left: true,
+                ..Default::default()
+            },
+        },
+        compute_info: Default::default(),
+        workgroup_size: None,
+        warnings: Vec::new(),
         frag_input: FragInput { inputs: Vec::new() },
         frag_output: FragOutput {
             outputs: vec![ShaderInterfaceDefEntry {
@@ -97,6 +126,9 @@ fn test_shade1() {
                 descriptions: HashMap::new(),
                 num_constants: 0,
                 pc_ranges: Vec::new(),
+                binding_info: HashMap::new(),
+                push_constants: Vec::new(),
+                spec_constants: Vec::new(),
             },
         },
         vert_input: VertInput {
@@ -116,6 +148,9 @@ fn test_shade1() {
                 descriptions: HashMap::new(),
                 num_constants: 0,
                 pc_ranges: Vec::new(),
+                binding_info: HashMap::new(),
+                push_constants: Vec::new(),
+                spec_constants: Vec::new(),
             },
         },
     };
@@ -128,6 +163,26 @@ fn test_shade2() {
     setup();
     let target = Entry {
         compute_layout: Default::default(),
+        vert_info: StageInfo {
+            capabilities: vec![Capability::Shader],
+            extensions: Vec::new(),
+            built_ins_read: Vec::new(),
+            built_ins_written: vec![BuiltIn::Position],
+            execution_modes: Default::default(),
+        },
+        frag_info: StageInfo {
+            capabilities: vec![Capability::Shader],
+            extensions: Vec::new(),
+            built_ins_read: Vec::new(),
+            built_ins_written: Vec::new(),
+            execution_modes: ExecutionModes {
+                origin_upper_left: true,
+                ..Default::default()
+            },
+        },
+        compute_info: Default::default(),
+        workgroup_size: None,
+        warnings: Vec::new(),
         frag_input: FragInput {
             inputs: vec![
                 ShaderInterfaceDefEntry {
@@ -161,6 +216,9 @@ fn test_shade2() {
                 descriptions: HashMap::new(),
                 num_constants: 0,
                 pc_ranges: Vec::new(),
+                binding_info: HashMap::new(),
+                push_constants: Vec::new(),
+                spec_constants: Vec::new(),
             },
         },
         vert_input: VertInput {
@@ -196,6 +254,9 @@ fn test_shade2() {
                 descriptions: HashMap::new(),
                 num_constants: 0,
                 pc_ranges: Vec::new(),
+                binding_info: HashMap::new(),
+                push_constants: Vec::new(),
+                spec_constants: Vec::new(),
             },
         },
     };
@@ -208,6 +269,26 @@ fn test_shade3() {
     setup();
     let target = Entry {
         compute_layout: Default::default(),
+        vert_info: StageInfo {
+            capabilities: vec![Capability::Shader],
+            extensions: Vec::new(),
+            built_ins_read: Vec::new(),
+            built_ins_written: vec![BuiltIn::Position],
+            execution_modes: Default::default(),
+        },
+        frag_info: StageInfo {
+            capabilities: vec![Capability::Shader],
+            extensions: Vec::new(),
+            built_ins_read: Vec::new(),
+            built_ins_written: Vec::new(),
+            execution_modes: ExecutionModes {
+                origin_upper_left: true,
+                ..Default::default()
+            },
+        },
+        compute_info: Default::default(),
+        workgroup_size: None,
+        warnings: Vec::new(),
         frag_input: FragInput { inputs: Vec::new() },
         frag_output: FragOutput {
             outputs: vec![ShaderInterfaceDefEntry {
@@ -247,6 +328,9 @@ fn test_shade3() {
                 .collect(),
                 num_constants: 0,
                 pc_ranges: Vec::new(),
+                binding_info: HashMap::new(),
+                push_constants: Vec::new(),
+                spec_constants: Vec::new(),
             },
         },
         vert_input: VertInput {
@@ -266,6 +350,9 @@ fn test_shade3() {
                 descriptions: HashMap::new(),
                 num_constants: 0,
                 pc_ranges: Vec::new(),
+                binding_info: HashMap::new(),
+                push_constants: Vec::new(),
+                spec_constants: Vec::new(),
             },
         },
     };
@@ -289,6 +376,26 @@ fn test_shade4() {
     setup();
     let target = Entry {
         compute_layout: Default::default(),
+        vert_info: StageInfo {
+            capabilities: vec![Capability::Shader],
+            extensions: Vec::new(),
+            built_ins_read: Vec::new(),
+            built_ins_written: vec![BuiltIn::Position],
+            execution_modes: Default::default(),
+        },
+        frag_info: StageInfo {
+            capabilities: vec![Capability::Shader],
+            extensions: Vec::new(),
+            built_ins_read: Vec::new(),
+            built_ins_written: Vec::new(),
+            execution_modes: ExecutionModes {
+                origin_upper_left: true,
+                ..Default::default()
+            },
+        },
+        compute_info: Default::default(),
+        workgroup_size: None,
+        warnings: Vec::new(),
         frag_input: FragInput { inputs: Vec::new() },
         frag_output: FragOutput {
             outputs: vec![ShaderInterfaceDefEntry {
@@ -305,12 +412,15 @@ fn test_shade4() {
                 num_constants: 1,
                 pc_ranges: vec![PipelineLayoutDescPcRange {
                     offset: 0,
-                    size: 16,
+                    size: 4,
                     stages: ShaderStages {
                         fragment: true,
                         ..ShaderStages::none()
                     },
                 }],
+                binding_info: HashMap::new(),
+                push_constants: Vec::new(),
+                spec_constants: Vec::new(),
             },
         },
         vert_input: VertInput {
@@ -330,6 +440,9 @@ fn test_shade4() {
                 descriptions: HashMap::new(),
                 num_constants: 0,
                 pc_ranges: Vec::new(),
+                binding_info: HashMap::new(),
+                push_constants: Vec::new(),
+                spec_constants: Vec::new(),
             },
         },
     };
@@ -343,3 +456,1015 @@ fn test_shade4() {
         &descriptor_layout(&target.frag_layout),
     );
 }
+
+fn parse_compute<T>(compute: T) -> shade_runner::Entry
+where
+    T: AsRef<Path>,
+{
+    let project_root = std::env::current_dir().expect("failed to get root directory");
+    let mut path = project_root.clone();
+    path.push(PathBuf::from("tests/shaders/"));
+    path.push(compute);
+    let shader = shade_runner::load_compute(path).expect("Failed to compile");
+    shade_runner::parse_compute(&shader).unwrap()
+}
+
+// SPIR-V assembled from the `.spvasm` file of the same name
+fn load_spirv<T>(file: T) -> Vec<u32>
+where
+    T: AsRef<Path>,
+{
+    let project_root = std::env::current_dir().expect("failed to get root directory");
+    let mut path = project_root.clone();
+    path.push(PathBuf::from("tests/shaders/"));
+    path.push(file);
+    let bytes = std::fs::read(path).expect("Failed to read SPIR-V");
+    bytes
+        .chunks(4)
+        .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+        .collect()
+}
+
+#[test]
+fn test_entry_points() {
+    setup();
+    let shader = compile("vert5.glsl", "frag1.glsl");
+    let entry_points = parse_entry_points(&shader.vertex).unwrap();
+    assert_eq!(entry_points.len(), 1);
+    let ep = &entry_points[0];
+    assert_eq!(ep.name, "main");
+    assert_eq!(ep.execution_model, ExecutionModel::Vertex);
+    do_test(
+        &ep.inputs,
+        &vec![ShaderInterfaceDefEntry {
+            location: 0..1,
+            format: Format::R32G32Sfloat,
+            name: Some(Cow::Borrowed("position")),
+        }],
+    );
+    let used = &ep.layout_data.descriptions[&0];
+    assert!(used.contains_key(&0));
+    assert!(!used.contains_key(&1));
+
+    let entry = shade_runner::parse(&shader).unwrap();
+    assert!(entry.vert_layout.layout_data.descriptions[&0].contains_key(&1));
+
+    let entry = parse_module(&shader.vertex, "main", "main");
+    assert!(matches!(entry, Err(Error::EntryPoint(_))));
+    let entry = parse_module(&shader.fragment, "main", "main");
+    assert!(entry.is_err());
+
+    let module = load_spirv("multi.spv");
+    let entry_points = parse_entry_points(&module).unwrap();
+    let summary = entry_points
+        .iter()
+        .map(|ep| (ep.name.as_str(), ep.execution_model))
+        .collect::<Vec<_>>();
+    do_test(
+        &summary,
+        &vec![("vs_main", ExecutionModel::Vertex), ("fs_main", ExecutionModel::Fragment)],
+    );
+    let uv = vec![ShaderInterfaceDefEntry {
+        location: 0..1,
+        format: Format::R32G32Sfloat,
+        name: Some(Cow::Borrowed("uv")),
+    }];
+    do_test(&entry_points[0].outputs, &uv);
+    do_test(&entry_points[1].inputs, &uv);
+    let bindings = |ep: &EntryPoint| {
+        let mut bindings = ep.layout_data.descriptions[&0].keys().copied().collect::<Vec<_>>();
+        bindings.sort();
+        bindings
+    };
+    do_test(&bindings(&entry_points[0]), &vec![0]);
+    do_test(&bindings(&entry_points[1]), &vec![1]);
+
+    let entry = parse_module(&module, "vs_main", "fs_main").unwrap();
+    do_test(
+        &entry.vert_input.inputs,
+        &vec![ShaderInterfaceDefEntry {
+            location: 0..1,
+            format: Format::R32G32Sfloat,
+            name: Some(Cow::Borrowed("position")),
+        }],
+    );
+    do_test(&entry.vert_output.outputs, &uv);
+    do_test(&entry.frag_input.inputs, &uv);
+    do_test(
+        &entry.frag_output.outputs,
+        &vec![ShaderInterfaceDefEntry {
+            location: 0..1,
+            format: Format::R32G32B32A32Sfloat,
+            name: Some(Cow::Borrowed("color")),
+        }],
+    );
+    assert!(entry.warnings.is_empty());
+    assert!(entry.frag_info.execution_modes.origin_upper_left);
+    let vertex = ShaderStages { vertex: true, ..ShaderStages::none() };
+    let fragment = ShaderStages { fragment: true, ..ShaderStages::none() };
+    let layout = entry.layout().unwrap();
+    do_test(&layout.descriptor(0, 0).map(|d| d.stages), &Some(vertex));
+    do_test(&layout.descriptor(0, 1).map(|d| d.stages), &Some(fragment));
+    assert!(matches!(
+        parse_module(&module, "fs_main", "vs_main"),
+        Err(Error::EntryPoint(_))
+    ));
+    assert!(matches!(
+        parse_entry_points(&module[..3]),
+        Err(Error::Reflect(ReflectError::NotSpirv))
+    ));
+    // The last instruction claims a word past the end
+    let mut malformed = module.clone();
+    *malformed.last_mut().unwrap() += 1 << 16;
+    let word = malformed.len() - 1;
+    assert!(matches!(
+        parse_entry_points(&malformed),
+        Err(Error::Reflect(ReflectError::Instruction { word: w })) if w == word
+    ));
+    // An OpEntryPoint with only its execution model and an OpFunction with no id
+    for truncated in &[vec![2 << 16 | 15, 0], vec![2 << 16 | 54, 1]] {
+        let mut malformed = module[..5].to_vec();
+        malformed.extend(truncated);
+        assert!(matches!(
+            parse_entry_points(&malformed),
+            Err(Error::Reflect(ReflectError::Instruction { word: 5 }))
+        ));
+    }
+}
+
+#[test]
+fn test_entry_layout() {
+    setup();
+    let entry = parse("vert6.glsl", "frag5.glsl");
+    let layout = entry.layout().unwrap();
+    let target = EntryLayout {
+        layout_data: LayoutData {
+            num_sets: 2,
+            num_bindings: vec![(0, 1), (1, 1)].into_iter().collect(),
+            descriptions: vec![
+                (
+                    0,
+                    vec![(
+                        0,
+                        DescriptorDesc {
+                            ty: DescriptorDescTy::Buffer(DescriptorBufferDesc {
+                                dynamic: Some(false),
+                                storage: false,
+                            }),
+                            array_count: 1,
+                            stages: ShaderStages {
+                                vertex: true,
+                                fragment: true,
+                                ..ShaderStages::none()
+                            },
+                            readonly: true,
+                        },
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+                (
+                    1,
+                    vec![(
+                        0,
+                        DescriptorDesc {
+                            ty: DescriptorDescTy::CombinedImageSampler(DescriptorImageDesc {
+                                sampled: true,
+                                dimensions: DescriptorImageDescDimensions::TwoDimensional,
+                                format: None,
+                                multisampled: false,
+                                array_layers: DescriptorImageDescArray::NonArrayed,
+                            }),
+                            array_count: 1,
+                            stages: ShaderStages {
+                                fragment: true,
+                                ..ShaderStages::none()
+                            },
+                            readonly: true,
+                        },
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            num_constants: 0,
+            pc_ranges: Vec::new(),
+            binding_info: HashMap::new(),
+            push_constants: Vec::new(),
+            spec_constants: Vec::new(),
+        },
+    };
+    for set in 0..2 {
+        do_test(&layout.descriptor(set, 0), &target.descriptor(set, 0));
+    }
+    do_test(&descriptor_layout(&layout), &descriptor_layout(&target));
+
+    let entry = parse("vert6.glsl", "frag6.glsl");
+    assert!(matches!(
+        entry.layout(),
+        Err(Error::Merge(MergeError::Descriptor { set: 0, binding: 0, .. }))
+    ));
+}
+
+#[test]
+fn test_push_constant_ranges() {
+    setup();
+    let vertex = PipelineLayoutDescPcRange {
+        offset: 0,
+        size: 64,
+        stages: ShaderStages {
+            vertex: true,
+            ..ShaderStages::none()
+        },
+    };
+    let entry = parse("vert7.glsl", "frag7.glsl");
+    do_test(&entry.vert_layout.push_constants_range(0), &Some(vertex));
+    let fragment = PipelineLayoutDescPcRange {
+        offset: 64,
+        size: 16,
+        stages: ShaderStages {
+            fragment: true,
+            ..ShaderStages::none()
+        },
+    };
+    do_test(&entry.frag_layout.push_constants_range(0), &Some(fragment));
+    let layout = entry.layout().unwrap();
+    assert_eq!(layout.num_push_constants_ranges(), 2);
+    do_test(&layout.push_constants_range(0), &Some(vertex));
+    do_test(&layout.push_constants_range(1), &Some(fragment));
+
+    let entry = parse("vert7.glsl", "frag8.glsl");
+    let layout = entry.layout().unwrap();
+    assert_eq!(layout.num_push_constants_ranges(), 1);
+    do_test(
+        &layout.push_constants_range(0),
+        &Some(PipelineLayoutDescPcRange {
+            offset: 0,
+            size: 80,
+            stages: ShaderStages {
+                vertex: true,
+                fragment: true,
+                ..ShaderStages::none()
+            },
+        }),
+    );
+
+    let shader = compile("vert7.glsl", "frag9.glsl");
+    assert!(matches!(
+        shade_runner::parse(&shader),
+        Err(Error::Merge(MergeError::PushConstant { offset: 0 }))
+    ));
+}
+
+#[test]
+fn test_interface_validation() {
+    setup();
+    let entry = parse("vert8.glsl", "frag10.glsl");
+    do_test(
+        &entry.warnings,
+        &vec![Warning::Unwritten {
+            location: 1,
+            name: "uv".to_string(),
+        }],
+    );
+
+    let shader = compile("vert8.glsl", "frag11.glsl");
+    let errors = match shade_runner::parse(&shader) {
+        Err(Error::Interface(errors)) => errors,
+        r => panic!("expected interface errors, got {:?}", r.map(|_| ())),
+    };
+    do_test(
+        &errors,
+        &vec![
+            InterfaceError::Format {
+                location: 0,
+                vertex: Format::R32G32B32A32Sfloat,
+                fragment: Format::R32G32B32Sfloat,
+            },
+            InterfaceError::Interpolation {
+                location: 1,
+                vertex: Interpolation::default(),
+                fragment: Interpolation {
+                    flat: true,
+                    ..Interpolation::default()
+                },
+            },
+            InterfaceError::Missing {
+                location: 3,
+                name: "fade".to_string(),
+            },
+        ],
+    );
+}
+
+#[test]
+fn test_attachment_validation() {
+    setup();
+    let entry = parse("vert1.glsl", "frag12.glsl");
+    entry
+        .frag_output
+        .check_attachments(&[Format::B8G8R8A8Unorm, Format::R32G32Sint])
+        .unwrap();
+    entry
+        .frag_output
+        .check_attachments(&[Format::A2B10G10R10UnormPack32, Format::R32G32Sint])
+        .unwrap();
+
+    let errors = match entry.frag_output.check_attachments(&[
+        Format::R8G8B8A8Uint,
+        Format::R32G32B32A32Sint,
+        Format::R8G8B8A8Unorm,
+    ]) {
+        Err(Error::Attachment(errors)) => errors,
+        r => panic!("expected attachment errors, got {:?}", r),
+    };
+    do_test(
+        &errors,
+        &vec![
+            AttachmentMismatch::Unwritten { location: 2 },
+            AttachmentMismatch::NumericType {
+                location: 0,
+                shader: FormatTy::Float,
+                attachment: FormatTy::Uint,
+            },
+            AttachmentMismatch::Components {
+                location: 1,
+                shader: 2,
+                attachment: 4,
+            },
+        ],
+    );
+
+    let errors = match entry.frag_output.check_attachments(&[Format::R16G16B16A16Sfloat]) {
+        Err(Error::Attachment(errors)) => errors,
+        r => panic!("expected attachment errors, got {:?}", r),
+    };
+    do_test(&errors, &vec![AttachmentMismatch::NoAttachment { location: 1 }]);
+}
+
+#[test]
+fn test_storage_images() {
+    setup();
+    let entry = parse_compute("compute1.glsl");
+    let image = |format, readonly| DescriptorDesc {
+        ty: DescriptorDescTy::Image(DescriptorImageDesc {
+            sampled: false,
+            dimensions: DescriptorImageDescDimensions::TwoDimensional,
+            format,
+            multisampled: false,
+            array_layers: DescriptorImageDescArray::NonArrayed,
+        }),
+        array_count: 1,
+        stages: ShaderStages::compute(),
+        readonly,
+    };
+    let layout = &entry.compute_layout;
+    do_test(&layout.descriptor(0, 0), &Some(image(Some(Format::R8G8B8A8Unorm), true)));
+    do_test(&layout.descriptor(0, 1), &Some(image(Some(Format::R32Sfloat), false)));
+    do_test(&layout.descriptor(0, 2), &Some(image(None, false)));
+}
+
+#[test]
+fn test_texel_buffers() {
+    setup();
+    let entry = parse_compute("compute2.glsl");
+    let texel_buffer = |storage, format| DescriptorDesc {
+        ty: DescriptorDescTy::TexelBuffer { storage, format },
+        array_count: 1,
+        stages: ShaderStages::compute(),
+        readonly: !storage,
+    };
+    let layout = &entry.compute_layout;
+    do_test(&layout.descriptor(0, 0), &Some(texel_buffer(false, None)));
+    do_test(
+        &layout.descriptor(0, 1),
+        &Some(texel_buffer(true, Some(Format::R16G16B16A16Sfloat))),
+    );
+}
+
+#[test]
+fn test_readonly_buffers() {
+    setup();
+    let entry = parse_compute("compute3.glsl");
+    let buffer = |storage, readonly| DescriptorDesc {
+        ty: DescriptorDescTy::Buffer(DescriptorBufferDesc {
+            dynamic: Some(false),
+            storage,
+        }),
+        array_count: 1,
+        stages: ShaderStages::compute(),
+        readonly,
+    };
+    let layout = &entry.compute_layout;
+    do_test(&layout.descriptor(0, 0), &Some(buffer(false, true)));
+    do_test(&layout.descriptor(0, 1), &Some(buffer(true, true)));
+    do_test(&layout.descriptor(0, 2), &Some(buffer(true, false)));
+    do_test(&layout.descriptor(0, 3), &Some(buffer(true, false)));
+}
+
+#[test]
+fn test_binding_stages() {
+    setup();
+    let vertex = ShaderStages {
+        vertex: true,
+        ..ShaderStages::none()
+    };
+    let entry = parse("vert5.glsl", "frag1.glsl");
+    let stages = |layout: &dyn PipelineLayoutDesc, binding| {
+        layout.descriptor(0, binding).map(|d| d.stages)
+    };
+    do_test(&stages(&entry.vert_layout, 0), &Some(vertex));
+    do_test(&stages(&entry.vert_layout, 1), &Some(ShaderStages::none()));
+    let layout = entry.layout().unwrap();
+    do_test(&stages(&layout, 0), &Some(vertex));
+    do_test(&stages(&layout, 1), &Some(ShaderStages::none()));
+}
+
+#[test]
+fn test_multi_location_interfaces() {
+    setup();
+    let entry = parse("vert9.glsl", "frag13.glsl");
+    let interface = |start, end, format, name| ShaderInterfaceDefEntry {
+        location: start..end,
+        format,
+        name: Some(Cow::Borrowed(name)),
+    };
+    do_test(
+        &entry.vert_input.inputs,
+        &vec![
+            interface(5, 7, Format::R32G32B32Sfloat, "normal"),
+            interface(1, 5, Format::R32G32B32A32Sfloat, "transform"),
+            interface(0, 1, Format::R32G32Sfloat, "position"),
+        ],
+    );
+    let outputs = vec![
+        interface(0, 3, Format::R32G32B32A32Sfloat, "weights"),
+        interface(3, 4, Format::R32G32B32A32Sfloat, "light.color"),
+        interface(4, 5, Format::R32G32Sfloat, "light.dir"),
+        interface(5, 6, Format::R32G32B32Sfloat, "blk.a"),
+        interface(6, 8, Format::R32G32Sfloat, "blk.b"),
+    ];
+    do_test(&entry.vert_output.outputs, &outputs);
+    do_test(&entry.frag_input.inputs, &outputs);
+    assert!(entry.warnings.is_empty());
+}
+
+#[test]
+fn test_vertex_input_formats() {
+    setup();
+    let interface = |start, end, format, name| ShaderInterfaceDefEntry {
+        location: start..end,
+        format,
+        name: Some(Cow::Borrowed(name)),
+    };
+    let entry = parse("vert10.glsl", "frag1.glsl");
+    do_test(
+        &entry.vert_input.inputs,
+        &vec![
+            interface(2, 3, Format::R16G16Sfloat, "uv"),
+            interface(3, 4, Format::R16G16B16A16Sint, "ids"),
+            interface(4, 5, Format::R64Sfloat, "weight"),
+            interface(5, 9, Format::R64G64B64Sfloat, "offsets"),
+            interface(0, 2, Format::R64G64B64Sfloat, "position"),
+        ],
+    );
+
+    let mut vert_input = parse("vert9.glsl", "frag13.glsl").vert_input;
+    vert_input.override_format(0, Format::R8G8Unorm).unwrap();
+    vert_input.override_format(2, Format::R8G8B8A8Snorm).unwrap();
+    do_test(
+        &vert_input.inputs,
+        &vec![
+            interface(5, 7, Format::R32G32B32Sfloat, "normal"),
+            interface(1, 2, Format::R32G32B32A32Sfloat, "transform"),
+            interface(2, 3, Format::R8G8B8A8Snorm, "transform"),
+            interface(3, 5, Format::R32G32B32A32Sfloat, "transform"),
+            interface(0, 1, Format::R8G8Unorm, "position"),
+        ],
+    );
+    assert!(matches!(
+        vert_input.override_format(5, Format::R8G8B8Uint),
+        Err(Error::Override(OverrideError::Incompatible { location: 5, .. }))
+    ));
+    assert!(matches!(
+        vert_input.override_format(7, Format::R8G8B8Unorm),
+        Err(Error::Override(OverrideError::Missing { location: 7 }))
+    ));
+    let mut vert_input = entry.vert_input;
+    assert!(vert_input.override_format(4, Format::R64Sfloat).is_ok());
+    assert!(vert_input.override_format(2, Format::R64G64Sfloat).is_err());
+}
+
+#[test]
+fn test_location_components() {
+    setup();
+    let interface = |start, end, format, name| ShaderInterfaceDefEntry {
+        location: start..end,
+        format,
+        name: Some(Cow::Borrowed(name)),
+    };
+    let entry = parse("vert11.glsl", "frag14.glsl");
+    let packed = vec![
+        interface(0, 1, Format::R32G32B32A32Sfloat, "uv, offset"),
+        interface(1, 2, Format::R32Sfloat, "fade"),
+    ];
+    do_test(&entry.vert_output.outputs, &packed);
+    do_test(&entry.frag_input.inputs, &packed);
+
+    // Inputs packed differently from the outputs feeding them
+    let entry = parse("vert16.glsl", "frag27.glsl");
+    assert!(entry.warnings.is_empty());
+    do_test(
+        &entry.vert_output.outputs,
+        &vec![
+            interface(0, 1, Format::R32G32B32A32Sfloat, "color"),
+            interface(1, 2, Format::R32G32B32A32Sfloat, "fade, offset"),
+            interface(2, 3, Format::R32G32Sfloat, "pairs"),
+            interface(3, 4, Format::R32G32B32Sfloat, "pairs, extra"),
+        ],
+    );
+    let formats = |entries: &[ShaderInterfaceDefEntry]| {
+        entries
+            .iter()
+            .map(|e| (e.location.clone(), e.format))
+            .collect::<Vec<_>>()
+    };
+    let outputs = formats(&entry.vert_output.outputs);
+    do_test(&formats(&entry.frag_input.inputs), &outputs);
+    do_test(&entry.frag_input.inputs[0].name, &Some(Cow::Borrowed("rg, ba")));
+
+    let shader = compile("vert11.glsl", "frag15.glsl");
+    let errors = match shade_runner::parse(&shader) {
+        Err(Error::Interface(errors)) => errors,
+        r => panic!("expected interface errors, got {:?}", r.map(|_| ())),
+    };
+    do_test(
+        &errors,
+        &vec![InterfaceError::Missing {
+            location: 1,
+            name: "offset".to_string(),
+        }],
+    );
+}
+
+#[test]
+fn test_runtime_arrays() {
+    setup();
+    let shader = compile("vert12.glsl", "frag16.glsl");
+    assert!(matches!(
+        shade_runner::parse(&shader),
+        Err(Error::RuntimeArray { set: 1, binding: 0 })
+    ));
+
+    let mut entry_points = parse_entry_points(&shader.fragment).unwrap();
+    let layout_data = &mut entry_points[0].layout_data;
+    do_test(&layout_data.descriptions[&1][&0].array_count, &0);
+    assert!(matches!(
+        layout_data.resolve_runtime_arrays(&RuntimeArrays::default()),
+        Err(Error::RuntimeArray { set: 1, binding: 0 })
+    ));
+    layout_data.resolve_runtime_arrays(&RuntimeArrays::max(8)).unwrap();
+    do_test(&layout_data.descriptions[&1][&0].array_count, &8);
+
+    let entry = parse_with_arrays(&shader, &RuntimeArrays::max(1024)).unwrap();
+    let layout = &entry.frag_layout;
+    do_test(&layout.descriptor(1, 0).map(|d| d.array_count), &Some(1024));
+    do_test(&layout.descriptor(1, 1).map(|d| d.array_count), &Some(1));
+    let info = &layout.layout_data.binding_info[&1];
+    assert!(info[&0].variable_count);
+    assert!(!info[&1].variable_count);
+
+    let arrays = RuntimeArrays::default().count_for(1, 0, 64);
+    let entry = parse_with_arrays(&shader, &arrays).unwrap();
+    let layout = entry.layout().unwrap();
+    do_test(&layout.descriptor(1, 0).map(|d| d.array_count), &Some(64));
+    assert!(layout.layout_data.binding_info[&1][&0].variable_count);
+
+    // The first load happens when the watch is created
+    let mut path = std::env::current_dir().expect("failed to get root directory");
+    path.push(PathBuf::from("tests/shaders/"));
+    let checks = Checks {
+        arrays: RuntimeArrays::max(16),
+        ..Checks::default()
+    };
+    let watch = Watch::create_with_checks(
+        path.join("vert12.glsl"),
+        path.join("frag16.glsl"),
+        Duration::from_millis(50),
+        checks,
+    )
+    .unwrap();
+    let message = watch.rx.recv().unwrap().unwrap();
+    let layout = &message.entry.frag_layout;
+    do_test(&layout.descriptor(1, 0).map(|d| d.array_count), &Some(16));
+}
+
+#[test]
+fn test_sparse_layout() {
+    setup();
+    let entry = parse("vert1.glsl", "frag17.glsl");
+    let layout = &entry.frag_layout;
+    assert_eq!(layout.num_sets(), 3);
+    assert_eq!(layout.num_bindings_in_set(0), Some(0));
+    assert_eq!(layout.num_bindings_in_set(1), Some(0));
+    assert_eq!(layout.num_bindings_in_set(2), Some(6));
+    assert_eq!(layout.num_bindings_in_set(3), None);
+    assert!(layout.descriptor(2, 0).is_some());
+    assert!(layout.descriptor(2, 3).is_none());
+    assert!(layout.descriptor(2, 5).is_some());
+
+    let layout = entry.layout().unwrap();
+    assert_eq!(layout.num_sets(), 3);
+    assert_eq!(layout.num_bindings_in_set(2), Some(6));
+}
+
+#[test]
+fn test_image_dimensions() {
+    setup();
+    let entry = parse("vert1.glsl", "frag18.glsl");
+    let layout = &entry.frag_layout;
+    let image = |binding| match layout.descriptor(0, binding).map(|d| d.ty) {
+        Some(DescriptorDescTy::CombinedImageSampler(image)) => (image.dimensions, image.array_layers),
+        ty => panic!("expected a combined image sampler, got {:?}", ty),
+    };
+    let arrayed = DescriptorImageDescArray::Arrayed { max_layers: None };
+    do_test(&image(0), &(DescriptorImageDescDimensions::TwoDimensional, arrayed));
+    do_test(&image(1), &(DescriptorImageDescDimensions::Cube, arrayed));
+    do_test(
+        &image(3),
+        &(DescriptorImageDescDimensions::TwoDimensional, DescriptorImageDescArray::NonArrayed),
+    );
+
+    let info = &layout.layout_data.binding_info[&0];
+    let image_info = |binding: usize| (info[&binding].depth_comparison, info[&binding].sampled_type);
+    do_test(&image_info(0), &(true, Some(SampledType::Float)));
+    do_test(&image_info(1), &(false, Some(SampledType::Float)));
+    do_test(&image_info(2), &(false, Some(SampledType::Uint)));
+    do_test(&image_info(3), &(false, Some(SampledType::Sint)));
+}
+
+#[test]
+fn test_block_members() {
+    setup();
+    let entry = parse("vert1.glsl", "frag19.glsl");
+    let info = &entry.frag_layout.layout_data.binding_info[&0];
+    let member = |name: &str, ty, offset, size| BlockMember {
+        name: name.to_string(),
+        ty,
+        offset,
+        size,
+        array: Vec::new(),
+        array_stride: 0,
+        matrix_stride: 0,
+        row_major: false,
+        members: Vec::new(),
+    };
+    let float = ScalarType::Float(32);
+    do_test(
+        &info[&0].members,
+        &vec![
+            BlockMember {
+                matrix_stride: 16,
+                row_major: true,
+                ..member("view", MemberType::Matrix { ty: float, columns: 4, rows: 4 }, 0, 64)
+            },
+            BlockMember {
+                matrix_stride: 16,
+                ..member("normal", MemberType::Matrix { ty: float, columns: 3, rows: 3 }, 64, 48)
+            },
+            BlockMember {
+                array: vec![2],
+                array_stride: 16,
+                members: vec![
+                    member("dir", MemberType::Vector(float, 3), 0, 12),
+                    member("power", MemberType::Scalar(float), 12, 4),
+                ],
+                ..member("lights", MemberType::Struct, 112, 32)
+            },
+            member("scale", MemberType::Vector(float, 2), 144, 8),
+            member("count", MemberType::Scalar(ScalarType::Sint(32)), 152, 4),
+        ],
+    );
+    do_test(
+        &info[&1].members,
+        &vec![
+            member("weight", MemberType::Scalar(float), 0, 4),
+            BlockMember {
+                array: vec![0],
+                array_stride: 16,
+                ..member("positions", MemberType::Vector(float, 4), 16, 0)
+            },
+        ],
+    );
+}
+
+#[test]
+fn test_push_constant_blocks() {
+    setup();
+    let entry = parse("vert13.glsl", "frag20.glsl");
+    let float = |name: &str, offset| BlockMember {
+        name: name.to_string(),
+        ty: MemberType::Scalar(ScalarType::Float(32)),
+        offset,
+        size: 4,
+        array: Vec::new(),
+        array_stride: 0,
+        matrix_stride: 0,
+        row_major: false,
+        members: Vec::new(),
+    };
+    let block = PushConstantBlock {
+        name: "pc".to_string(),
+        type_name: "PushConstantData".to_string(),
+        size: 32,
+        members: vec![
+            float("time", 0),
+            BlockMember {
+                ty: MemberType::Struct,
+                size: 12,
+                members: vec![float("amplitude", 0), float("frequency", 4)],
+                ..float("wave", 4)
+            },
+            BlockMember {
+                ty: MemberType::Vector(ScalarType::Float(32), 4),
+                size: 16,
+                ..float("tint", 16)
+            },
+        ],
+    };
+    do_test(&entry.frag_layout.layout_data.push_constants, &vec![block.clone()]);
+    do_test(&entry.push_constants(), &vec![block.clone()]);
+    do_test(&entry.layout().unwrap().layout_data.push_constants, &vec![block]);
+}
+
+#[test]
+fn test_spec_constants() {
+    setup();
+    let entry = parse_compute("compute4.glsl");
+    let constant = |id, name: &str, ty, default| SpecConstant {
+        id,
+        name: name.to_string(),
+        ty,
+        default,
+    };
+    let constants = vec![
+        constant(0, "use_scale", ScalarType::Bool, SpecValue::Bool(true)),
+        constant(1, "offset", ScalarType::Sint(32), SpecValue::Sint(-3)),
+        constant(2, "count", ScalarType::Uint(32), SpecValue::Uint(16)),
+        constant(5, "scale", ScalarType::Float(32), SpecValue::Float(0.5)),
+    ];
+    do_test(&entry.spec_constants(), &constants);
+    let mut values = SpecConstants::new(&constants).unwrap();
+    values.set("count", SpecValue::Uint(256)).unwrap();
+    values.set_id(1, SpecValue::Sint(7)).unwrap();
+    assert_eq!(values.get("count"), Some(SpecValue::Uint(256)));
+    assert_eq!(values.get("offset"), Some(SpecValue::Sint(7)));
+    assert_eq!(values.get("scale"), Some(SpecValue::Float(0.5)));
+    match values.set("scale", SpecValue::Uint(1)) {
+        Err(Error::SpecConstant(SpecConstantError::Type { .. })) => (),
+        r => panic!("expected a type error, got {:?}", r),
+    }
+    match values.set("count", SpecValue::Uint(1 << 40)) {
+        Err(Error::SpecConstant(SpecConstantError::Type { .. })) => (),
+        r => panic!("expected a type error, got {:?}", r),
+    }
+    match values.set("missing", SpecValue::Bool(false)) {
+        Err(Error::SpecConstant(SpecConstantError::Missing(_))) => (),
+        r => panic!("expected a missing error, got {:?}", r),
+    }
+    let entry = SpecConstants::descriptors()
+        .iter()
+        .find(|d| d.constant_id == 5)
+        .unwrap();
+    assert_eq!((entry.offset, entry.size), (20, 4));
+    // Constants without a slot keep their defaults
+    let constants = vec![
+        constant(0, "precise", ScalarType::Float(64), SpecValue::Float(0.25)),
+        constant(70, "far", ScalarType::Uint(32), SpecValue::Uint(9)),
+    ];
+    let mut values = SpecConstants::new(&constants).unwrap();
+    assert_eq!(values.get("precise"), Some(SpecValue::Float(0.25)));
+    assert_eq!(values.get("far"), Some(SpecValue::Uint(9)));
+    match values.set("precise", SpecValue::Float(1.0)) {
+        Err(Error::SpecConstant(SpecConstantError::Unsupported { id: 0, .. })) => (),
+        r => panic!("expected an unsupported error, got {:?}", r),
+    }
+    match values.set_id(70, SpecValue::Uint(1)) {
+        Err(Error::SpecConstant(SpecConstantError::Unsupported { id: 70, .. })) => (),
+        r => panic!("expected an unsupported error, got {:?}", r),
+    }
+}
+
+#[test]
+fn test_workgroup_size() {
+    setup();
+    let entry = parse_compute("compute3.glsl");
+    let fixed = WorkgroupSize {
+        size: [64, 1, 1],
+        spec_ids: [None; 3],
+    };
+    do_test(&entry.workgroup_size, &Some(fixed));
+    assert_eq!(fixed.dispatch([1000, 1, 1]), [16, 1, 1]);
+    assert_eq!(fixed.dispatch([u32::MAX, 0, 1]), [1 << 26, 0, 1]);
+    let entry = parse_compute("compute5.glsl");
+    let size = entry.workgroup_size.unwrap();
+    do_test(&size, &WorkgroupSize {
+        size: [16, 4, 1],
+        spec_ids: [Some(0), None, None],
+    });
+    assert_eq!(size.dispatch([100, 10, 1]), [7, 3, 1]);
+    let mut constants = SpecConstants::new(&entry.spec_constants()).unwrap();
+    constants.set_id(0, SpecValue::Uint(32)).unwrap();
+    assert_eq!(size.specialized(&constants), [32, 4, 1]);
+    assert_eq!(size.dispatch_specialized(&constants, [100, 10, 1]), [4, 3, 1]);
+}
+
+#[test]
+fn test_device_requirements() {
+    setup();
+    let entry = parse_compute("compute6.glsl");
+    do_test(&entry.compute_info, &StageInfo {
+        capabilities: vec![
+            Capability::Shader,
+            Capability::Int64,
+            Capability::StorageImageWriteWithoutFormat,
+            Capability::SubgroupBallotKHR,
+        ],
+        extensions: vec!["SPV_KHR_shader_ballot".to_string()],
+        built_ins_read: vec![BuiltIn::GlobalInvocationId],
+        built_ins_written: Vec::new(),
+        execution_modes: Default::default(),
+    });
+    let features = Features {
+        shader_int64: true,
+        shader_storage_image_write_without_format: true,
+        ..Features::none()
+    };
+    assert_eq!(entry.required_features(), features);
+    let ballot = CString::new("VK_EXT_shader_subgroup_ballot").unwrap();
+    let extensions = RawDeviceExtensions::new(vec![ballot]);
+    assert_eq!(entry.required_extensions(), extensions);
+    assert!(entry.check_device(&features, &extensions).is_ok());
+    match entry.check_device(&Features::none(), &(&DeviceExtensions::none()).into()) {
+        Err(Error::Device { features: f, extensions: e }) => {
+            assert_eq!(f, features);
+            assert_eq!(e, extensions);
+        }
+        r => panic!("expected missing requirements, got {:?}", r),
+    }
+    let info = StageInfo {
+        extensions: vec![
+            "SPV_KHR_post_depth_coverage".to_string(),
+            "SPV_KHR_storage_buffer_storage_class".to_string(),
+            "SPV_GOOGLE_user_type".to_string(),
+        ],
+        ..StageInfo::default()
+    };
+    let expected = RawDeviceExtensions::new(vec![
+        CString::new("VK_EXT_post_depth_coverage").unwrap(),
+        CString::new("VK_KHR_storage_buffer_storage_class").unwrap(),
+    ]);
+    assert_eq!(info.required_extensions(), expected);
+    let entry = parse("vert10.glsl", "frag1.glsl");
+    let storage = CString::new("VK_KHR_16bit_storage").unwrap();
+    let extensions = RawDeviceExtensions::new(vec![storage.clone()]);
+    assert_eq!(entry.required_extensions(), extensions);
+    let none = (&DeviceExtensions::none()).into();
+    assert!(entry.check_device(&entry.required_features(), &none).is_err());
+    let info = StageInfo {
+        capabilities: vec![
+            Capability::StorageInputOutput16,
+            Capability::StoragePushConstant8,
+        ],
+        ..StageInfo::default()
+    };
+    let eight = CString::new("VK_KHR_8bit_storage").unwrap();
+    let expected = RawDeviceExtensions::new(vec![storage, eight]);
+    assert_eq!(info.required_extensions(), expected);
+}
+
+#[test]
+fn test_built_ins() {
+    setup();
+    let entry = parse("vert14.glsl", "frag21.glsl");
+    do_test(&entry.vert_info.built_ins_read, &vec![BuiltIn::InstanceIndex]);
+    do_test(
+        &entry.vert_info.built_ins_written,
+        &vec![BuiltIn::Position, BuiltIn::PointSize],
+    );
+    do_test(
+        &entry.frag_info.built_ins_read,
+        &vec![BuiltIn::FragCoord, BuiltIn::SampleId],
+    );
+    do_test(&entry.frag_info.built_ins_written, &vec![BuiltIn::FragDepth]);
+}
+
+#[test]
+fn test_execution_modes() {
+    setup();
+    let entry = parse("vert1.glsl", "frag22.glsl");
+    do_test(&entry.vert_info.execution_modes, &ExecutionModes::default());
+    do_test(&entry.frag_info.execution_modes, &ExecutionModes {
+        early_fragment_tests: true,
+        origin_upper_left: true,
+        ..Default::default()
+    });
+    let entry = parse("vert1.glsl", "frag23.glsl");
+    do_test(&entry.frag_info.execution_modes, &ExecutionModes {
+        depth_replacing: true,
+        depth: Some(DepthMode::Greater),
+        origin_upper_left: true,
+        ..Default::default()
+    });
+    let entry_points = parse_entry_points(&load_spirv("pixel_center.spv")).unwrap();
+    do_test(&entry_points[0].info.execution_modes, &ExecutionModes {
+        origin_upper_left: true,
+        pixel_center_integer: true,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn test_bindings_by_name() {
+    setup();
+    let entry = parse("vert15.glsl", "frag24.glsl");
+    let lookup = |name| {
+        entry
+            .binding_by_name(name)
+            .map(|b| (b.stages, b.set, b.binding))
+    };
+    let vertex = ShaderStages { vertex: true, ..ShaderStages::none() };
+    let fragment = ShaderStages { fragment: true, ..ShaderStages::none() };
+    do_test(&lookup("u_material"), &Some((fragment, 1, 3)));
+    do_test(&lookup("albedo"), &Some((fragment, 1, 0)));
+    do_test(&lookup("Lights"), &Some((fragment, 2, 0)));
+    do_test(&lookup("camera"), &Some((vertex | fragment, 0, 0)));
+    do_test(&lookup("missing"), &None);
+    let camera = entry.binding_by_name("camera").unwrap();
+    assert_eq!(camera.descriptor.stages, vertex | fragment);
+    assert_eq!(entry.binding_name(1, 3), Some("u_material"));
+    assert_eq!(entry.binding_name(2, 0), Some("Lights"));
+    assert_eq!(entry.binding_name(1, 1), None);
+}
+
+#[test]
+fn test_statistics() {
+    setup();
+    let shader = compile("vert1.glsl", "frag25.glsl");
+    let stats = Statistics::new(&shader).unwrap();
+    assert!(stats.compute.is_none());
+    let fragment = stats.fragment.unwrap();
+    assert_eq!(fragment.words, shader.fragment.len());
+    do_test(
+        &(fragment.functions, fragment.loops, fragment.branches),
+        &(1, 1, 2),
+    );
+    assert_eq!(fragment.texture_samples, 2);
+    assert_eq!(fragment.resources, 2);
+    assert_eq!(fragment.classes.image, 2);
+    let vertex = stats.vertex.unwrap();
+    assert_eq!((vertex.texture_samples, vertex.resources), (0, 0));
+
+    let stats = ShaderStats::new(&load_spirv("multi.spv")).unwrap();
+    do_test(
+        &stats.classes,
+        &OpcodeClasses {
+            arithmetic: 1,
+            logic: 1,
+            conversion: 1,
+            composite: 2,
+            memory: 8,
+            image: 1,
+            atomic: 0,
+            control_flow: 4,
+            other: 59,
+        },
+    );
+    assert_eq!(stats.instructions, 77);
+
+    let budget = Budget {
+        texture_samples: Some(2),
+        loops: Some(1),
+        ..Budget::default()
+    };
+    assert!(budget.check_shaders(&shader).is_ok());
+    let budget = Budget {
+        texture_samples: Some(1),
+        resources: Some(4),
+        ..Budget::default()
+    };
+    match budget.check_shaders(&shader) {
+        Err(Error::Budget(over)) => do_test(
+            &over,
+            &vec![OverBudget {
+                stage: Stage::Fragment,
+                limit: Limit::TextureSamples,
+                value: 2,
+                max: 1,
+            }],
+        ),
+        r => panic!("expected the budget to be exceeded, got {:?}", r),
+    }
+}